@@ -0,0 +1,3417 @@
+//! The command line interface of `hpm`, built on top of [`crate::process`].
+//!
+//! It is the same with the `hpm` binary, including the interactive mode (`-i`),
+//! so the library consumers can embed it via [`run`] or [`run_from`].
+//!
+//! [`run`]: crate::cli::run
+//! [`run_from`]: crate::cli::run_from
+//! [`crate::process`]: crate::process
+
+use crate::{
+    Process,
+    process::{Runner, binary_exists},
+};
+use anstyle::{AnsiColor, Style};
+use clap::{
+    ArgAction, CommandFactory, Parser, Subcommand, ValueEnum, builder::BoolishValueParser,
+    error::ErrorKind,
+};
+use clap_complete::Shell;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::HashMap;
+use std::fmt::{self, Debug};
+use std::io::IsTerminal;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::{Duration, Instant, SystemTime};
+
+const PROGRAM: &str = "hpm";
+const PKEXEC_DISMISSED_ECODE: i32 = 126;
+const INTERACTIVE_ATTEMPTS: u8 = 3;
+// Logout, the least destructive choice.
+const DEFAULT_CHOICE: u8 = 2;
+const SCHEDULED_SHUTDOWN_PATH: &str = "/run/systemd/shutdown/scheduled";
+const RETRY_BACKOFF: Duration = Duration::from_secs(1);
+const UPTIME_PATH: &str = "/proc/uptime";
+
+/// Parses the arguments of the process, runs the given command and reports its outcome.
+/// It is a shorthand for [`run_from`] with [`std::env::args_os`].
+///
+/// The errors are printed to [`std::io::stderr`] (or to [`std::io::stdout`] as JSON with `--format json`),
+/// and mapped to the returned [`std::process::ExitCode`].
+///
+/// [`std::io::stdout`]: std::io::stdout
+/// [`std::io::stderr`]: std::io::stderr
+/// [`std::process::ExitCode`]: std::process::ExitCode
+/// [`std::env::args_os`]: std::env::args_os
+/// [`run_from`]: crate::cli::run_from
+pub fn run() -> ExitCode {
+    run_from(std::env::args_os())
+}
+
+/// Parses the given arguments, runs the given command and reports its outcome the same way as [`run`].
+/// The first argument is the program name, e.g. `["hpm", "-i"]` presents the interactive menu.
+///
+/// [`run`]: crate::cli::run
+pub fn run_from<I, T>(itr: I) -> ExitCode
+where
+    I: IntoIterator<Item = T>,
+    T: Into<std::ffi::OsString> + Clone,
+{
+    let args = Args::parse_from(itr);
+    let format = args.format;
+    let color = args.color;
+    let json_errors = args.json_errors;
+    let (retries, timeout, no_validate) = (args.retries, args.timeout, args.no_validate);
+
+    let mut runner = |process: &mut Process| {
+        if let Some(timeout) = timeout {
+            process.timeout(timeout);
+        }
+
+        match retries {
+            0 if no_validate => process.exec_unchecked(),
+            0 => process.exec(),
+            retries => process.exec_with_retries(retries, RETRY_BACKOFF),
+        }
+    };
+
+    let run_result = Config::load()
+        .map_err(Into::into)
+        .and_then(|config| try_run(args, config, &mut runner));
+
+    match run_result {
+        Ok(ecode) => ExitCode::from(ecode),
+        Err(hpm_err) => {
+            let ecode = exit_code(hpm_err.as_ref());
+            let message = match error_hint(hpm_err.as_ref()) {
+                Some(hint) => format!("{hpm_err} ({hint})"),
+                None => hpm_err.to_string(),
+            };
+
+            match format {
+                _ if json_errors => eprintln!(
+                    "{}",
+                    serde_json::json!({
+                        "error_kind": error_kind(hpm_err.as_ref()),
+                        "code": ecode,
+                        "message": message,
+                    })
+                ),
+                OutputFormat::Text => eprintln!(
+                    "{}",
+                    paint(
+                        format!("{PROGRAM}: {message}").trim_end(),
+                        AnsiColor::Red.on_default(),
+                        color.enabled(&std::io::stderr()),
+                    )
+                ),
+                OutputFormat::Json => println!(
+                    "{}",
+                    serde_json::json!({
+                        "success": false,
+                        "error": message,
+                        "exit_code": ecode,
+                    })
+                ),
+            }
+
+            ExitCode::from(ecode)
+        }
+    }
+}
+
+/// Guides the user on the errors that the library cannot know how to fix, e.g. a binary that cannot be executed.
+fn error_hint(hpm_err: &(dyn std::error::Error + 'static)) -> Option<&'static str> {
+    match hpm_err.downcast_ref::<crate::Error>()? {
+        crate::Error::FailedToExecProcess(_, err)
+            if err.kind() == std::io::ErrorKind::PermissionDenied =>
+        {
+            Some("check that the binary and its interpreter are executable")
+        }
+        _ => None,
+    }
+}
+
+/// Names the variant of the error, e.g. `Exec` for [`crate::Error::Exec`].
+fn error_kind(hpm_err: &(dyn std::error::Error + 'static)) -> &'static str {
+    if let Some(err) = hpm_err.downcast_ref::<crate::Error>() {
+        return match err {
+            crate::Error::BinaryDoesNotExist(_) => "BinaryDoesNotExist",
+            crate::Error::EmptyProgram => "EmptyProgram",
+            crate::Error::FailedToExecProcess(_, _) => "FailedToExecProcess",
+            crate::Error::Exec { .. } => "Exec",
+            crate::Error::Interrupted(_) => "Interrupted",
+            crate::Error::TimedOut(_) => "TimedOut",
+        };
+    }
+
+    if let Some(err) = hpm_err.downcast_ref::<Error>() {
+        return match err {
+            Error::FailedToWriteStdout(_) => "FailedToWriteStdout",
+            Error::FailedToWriteFile(_, _) => "FailedToWriteFile",
+            Error::FailedToReadStdin(_) => "FailedToReadStdin",
+            Error::InvalidUserAnswer => "InvalidUserAnswer",
+            Error::NoInput => "NoInput",
+            Error::MissingEnv(_) => "MissingEnv",
+            Error::UnschedulableCommand(_) => "UnschedulableCommand",
+            Error::UnverifiableCommand(_) => "UnverifiableCommand",
+            Error::UnannounceableCommand(_) => "UnannounceableCommand",
+            Error::UnverifiedRestart(_) => "UnverifiedRestart",
+            Error::AuthorizationDismissed => "AuthorizationDismissed",
+            Error::Interrupted => "Interrupted",
+            Error::FailedToReadConfig(_, _) => "FailedToReadConfig",
+            Error::FailedToListUsers(_) => "FailedToListUsers",
+            Error::FailedToNotify(_) => "FailedToNotify",
+            Error::MismatchedConfirmation(_) => "MismatchedConfirmation",
+            Error::SystemdIsNotInit(_) => "SystemdIsNotInit",
+            Error::FailedToListSessions(_) => "FailedToListSessions",
+            Error::RemainingSessions(_) => "RemainingSessions",
+            Error::InvalidReason(_) => "InvalidReason",
+            Error::UnsafeExec(_) => "UnsafeExec",
+            Error::UnsupportedFirmwareSetup => "UnsupportedFirmwareSetup",
+            Error::Aborted(_) => "Aborted",
+            Error::FailedToListInhibitors(_) => "FailedToListInhibitors",
+            Error::UnsupportedCommand(_) => "UnsupportedCommand",
+        };
+    }
+
+    "Other"
+}
+
+fn exit_code(hpm_err: &(dyn std::error::Error + 'static)) -> u8 {
+    if let Some(err) = hpm_err.downcast_ref::<crate::Error>() {
+        return err.code();
+    }
+
+    if let Some(err) = hpm_err.downcast_ref::<Error>() {
+        return match err {
+            Error::FailedToWriteStdout(_) => 1u8,
+            Error::FailedToWriteFile(_, _) => 1u8,
+            Error::FailedToReadStdin(_) => 1u8,
+            Error::InvalidUserAnswer => 1u8,
+            Error::NoInput => 1u8,
+            Error::MissingEnv(_) => 1u8,
+            Error::UnschedulableCommand(_) => 1u8,
+            Error::UnverifiableCommand(_) => 1u8,
+            Error::UnannounceableCommand(_) => 1u8,
+            Error::UnverifiedRestart(_) => 1u8,
+            Error::AuthorizationDismissed => PKEXEC_DISMISSED_ECODE as u8,
+            Error::Interrupted => 130u8,
+            Error::FailedToReadConfig(_, _) => 1u8,
+            Error::FailedToListUsers(err) => err.code(),
+            Error::FailedToNotify(err) => err.code(),
+            Error::MismatchedConfirmation(_) => 1u8,
+            Error::SystemdIsNotInit(err) => err.code(),
+            Error::FailedToListSessions(err) => err.code(),
+            Error::RemainingSessions(_) => 1u8,
+            Error::InvalidReason(_) => 1u8,
+            Error::UnsafeExec(_) => 1u8,
+            Error::UnsupportedFirmwareSetup => 1u8,
+            Error::Aborted(_) => 1u8,
+            Error::FailedToListInhibitors(err) => err.code(),
+            Error::UnsupportedCommand(_) => 1u8,
+        };
+    }
+
+    2u8
+}
+
+#[derive(Debug, Parser)]
+#[command(version, about, long_about = None)]
+struct Args {
+    /// Open interactive mode.
+    #[arg(short, long)]
+    interactive: bool,
+
+    /// Ask for the reason after kill, halt or restart is selected in interactive mode, an empty reason sends none.
+    #[arg(long, requires = "interactive", conflicts_with_all = ["reason", "no_wall"])]
+    ask_reason: bool,
+
+    /// The choice to select by pressing Enter in interactive mode, by its index or its name.
+    #[arg(long, value_name = "COMMAND", value_parser = parse_choice, requires = "interactive")]
+    default: Option<u8>,
+
+    /// List the commands along with what they run on this host.
+    #[arg(short, long)]
+    list: bool,
+
+    /// Print the backend, the paths of the programs it runs and the target OS.
+    #[arg(long)]
+    backend_info: bool,
+
+    /// Print the effective settings, i.e. the flags merged over $HPM_BACKEND and the config, as TOML (or JSON with --format json).
+    #[arg(long, conflicts_with = "backend_info")]
+    dump_config: bool,
+
+    /// Print the command instead of executing it.
+    #[arg(short = 'n', long, global = true)]
+    dry_run: bool,
+
+    /// A testing aid: report the command as a success without executing it, e.g. `--format json` prints
+    /// the same JSON object as a successful run with an empty stdout.
+    #[arg(long, env = "HPM_PRETEND", value_parser = BoolishValueParser::new(), hide = true, global = true)]
+    pretend_success: bool,
+
+    /// Print logs to stderr, repeat for more details (-v, -vv, -vvv).
+    #[arg(short, long, action = ArgAction::Count, global = true)]
+    verbose: u8,
+
+    /// Do not print the output of the command.
+    #[arg(short, long, global = true)]
+    quiet: bool,
+
+    /// Write the output of the command to the given file instead of stdout, the file is created or truncated.
+    #[arg(long, value_name = "PATH", global = true)]
+    output_file: Option<PathBuf>,
+
+    /// Skip the confirmation prompts. Cannot be used with --interactive.
+    #[arg(short = 'y', long, visible_alias = "yes", global = true)]
+    assume_yes: bool,
+
+    /// Confirm kill, halt or restart by typing the name of the command instead of y/N. Cannot be used with --assume-yes.
+    #[arg(long, conflicts_with = "assume_yes", global = true)]
+    confirm_typing: bool,
+
+    /// Ignore the inhibitor locks of systemd on kill, halt or restart. Unlike --assume-yes, it does not skip the confirmation.
+    #[arg(long, global = true)]
+    force: bool,
+
+    /// Do not send a wall message before kill, halt or restart.
+    #[arg(long, global = true)]
+    no_wall: bool,
+
+    /// Print the exit code of the executed command to stderr as `exit: N`.
+    #[arg(long, global = true)]
+    print_exit_code: bool,
+
+    /// Retry the command up to N times when it fails, e.g. while a session is being torn down.
+    #[arg(long, value_name = "N", default_value_t = 0, global = true)]
+    retries: u32,
+
+    /// Exit successfully without running kill, halt or restart when a shutdown is already scheduled.
+    #[arg(long, global = true)]
+    idempotent: bool,
+
+    /// Exit with the exit code of the command even when it is non-zero, instead of failing, e.g. for an expected non-zero code.
+    #[arg(long, conflicts_with_all = ["retries", "verify"], global = true)]
+    propagate_exit_code: bool,
+
+    /// Allow the exec subcommand to run an arbitrary command, it is refused otherwise.
+    #[arg(long, global = true)]
+    unsafe_exec: bool,
+
+    /// Run the command without looking it up in $PATH first, a missing command is reported by the execution instead.
+    #[arg(long, conflicts_with_all = ["retries", "verify"], global = true)]
+    no_validate: bool,
+
+    /// Kill the command if it does not finish within the given duration (e.g. 30s, 1m), per attempt with --retries.
+    #[arg(long, value_name = "DURATION", value_parser = parse_delay, conflicts_with = "verify", global = true)]
+    timeout: Option<Duration>,
+
+    /// Start the restart detached and wait until it takes effect, e.g. before an SSH session drops.
+    #[arg(long, global = true)]
+    verify: bool,
+
+    /// How often --verify and logout --count check whether the command took effect.
+    #[arg(long, value_name = "DURATION", value_parser = parse_delay, default_value = "1s", global = true)]
+    poll_interval: Duration,
+
+    /// How long --verify and logout --count wait for the command to take effect.
+    #[arg(long, value_name = "DURATION", value_parser = parse_delay, default_value = "60s", global = true)]
+    poll_timeout: Duration,
+
+    /// Run the command via sudo.
+    #[arg(long, global = true)]
+    sudo: bool,
+
+    /// Run the command via pkexec (Polkit).
+    #[arg(long, global = true, conflicts_with = "sudo")]
+    pkexec: bool,
+
+    /// Schedule kill or restart after the given duration (e.g. 10m, 1h30m).
+    #[arg(long, value_name = "DURATION", value_parser = parse_delay, global = true)]
+    after: Option<Duration>,
+
+    /// Send the given message to the logged-in users via wall before running the command.
+    #[arg(long, value_name = "TEXT", value_parser = parse_message, global = true)]
+    notify: Option<String>,
+
+    /// Abort before running the command when a step would only warn otherwise, i.e. a failed --notify or inhibitor listing.
+    #[arg(long, global = true)]
+    strict: bool,
+
+    /// How long to wait after --notify before running the command.
+    #[arg(long, value_name = "DURATION", value_parser = humantime::parse_duration, default_value = "30s", requires = "notify", global = true)]
+    notify_delay: Duration,
+
+    /// The reason of kill or restart, sent to the logged-in users as the wall message of shutdown.
+    #[arg(long, value_name = "TEXT", value_parser = parse_message, conflicts_with = "no_wall", global = true)]
+    reason: Option<String>,
+
+    /// The backend to build the commands with, detected from $PATH by default.
+    #[arg(long, value_enum, env = "HPM_BACKEND", global = true)]
+    backend: Option<Backend>,
+
+    /// The output format.
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text, global = true)]
+    format: OutputFormat,
+
+    /// Print the errors to stderr as JSON objects, e.g. {"error_kind":"Exec","code":4,"message":"..."}.
+    #[arg(long, global = true)]
+    json_errors: bool,
+
+    /// When to color the interactive menu and the error messages.
+    #[arg(long, value_enum, default_value_t = ColorChoice::Auto, global = true)]
+    color: ColorChoice,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum Backend {
+    /// systemctl and loginctl.
+    Systemd,
+
+    /// shutdown and pkill, for hosts without systemd.
+    Sysv,
+
+    /// Print the systemd commands with echo instead of running them, e.g. `echo systemctl poweroff`.
+    Echo,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum OutputFormat {
+    /// Print the output of the command as is.
+    Text,
+
+    /// Print the outcome of the command as a JSON object.
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum ColorChoice {
+    /// Color when the output is a terminal and $NO_COLOR is not set.
+    Auto,
+
+    /// Always color.
+    Always,
+
+    /// Never color.
+    Never,
+}
+
+impl ColorChoice {
+    fn enabled(self, stream: &impl IsTerminal) -> bool {
+        match self {
+            ColorChoice::Auto => {
+                std::env::var_os("NO_COLOR").is_none_or(|no_color| no_color.is_empty())
+                    && stream.is_terminal()
+            }
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+}
+
+fn paint(text: impl fmt::Display, style: Style, enabled: bool) -> String {
+    match enabled {
+        true => format!("{style}{text}{style:#}"),
+        false => text.to_string(),
+    }
+}
+
+/// The defaults of the flags, read from `$XDG_CONFIG_HOME/hpm/config.toml`
+/// (or `~/.config/hpm/config.toml`). The flags given to `hpm` take precedence.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Config {
+    sudo: bool,
+    no_wall: bool,
+    assume_yes: bool,
+    #[serde(deserialize_with = "deserialize_choice")]
+    default: Option<u8>,
+}
+
+fn deserialize_choice<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u8>, D::Error> {
+    Option::<String>::deserialize(deserializer)?
+        .map(|choice| parse_choice(&choice).map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+impl Config {
+    fn path() -> Option<PathBuf> {
+        let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+
+        Some(config_dir.join(PROGRAM).join("config.toml"))
+    }
+
+    fn load() -> Result<Config, Error> {
+        let Some(path) = Config::path() else {
+            return Ok(Config::default());
+        };
+
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(Config::default());
+            }
+            Err(err) => return Err(Error::FailedToReadConfig(path, err.to_string())),
+        };
+
+        toml::from_str(&content).map_err(|err| Error::FailedToReadConfig(path, err.to_string()))
+    }
+
+    fn apply(self, args: &mut Args) {
+        args.sudo |= self.sudo && !args.pkexec;
+        args.no_wall |= self.no_wall;
+        // The explicit flags that ask for a confirmation are not overridden, e.g. --interactive conflicts with --assume-yes.
+        args.assume_yes |= self.assume_yes && !args.confirm_typing && !args.interactive;
+        args.default = args.default.or(self.default);
+    }
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Power off the system.
+    #[command(visible_aliases = ["poweroff", "shutdown"])]
+    Kill,
+
+    /// Halt the system without powering it off, e.g. for hardware debugging.
+    Halt,
+
+    /// Restart the system.
+    #[command(visible_alias = "reboot")]
+    Restart(RestartArgs),
+
+    /// Logout from the current $USER.
+    #[command(visible_alias = "logoff")]
+    Logout(LogoutArgs),
+
+    /// Suspend the system.
+    Suspend,
+
+    /// Hibernate the system.
+    Hibernate,
+
+    /// Hibernate and suspend the system.
+    HybridSleep,
+
+    /// Lock all sessions.
+    Lock,
+
+    /// Cancel a scheduled kill or restart.
+    Cancel,
+
+    /// Show the scheduled shutdown and the uptime.
+    Status,
+
+    /// Run the given command the same way as the others, e.g. `hpm --unsafe-exec exec -- systemctl suspend-then-hibernate`.
+    /// Requires --unsafe-exec.
+    Exec {
+        #[arg(
+            value_name = "COMMAND",
+            required = true,
+            trailing_var_arg = true,
+            allow_hyphen_values = true
+        )]
+        argv: Vec<String>,
+    },
+
+    /// Print the completion script for the given shell.
+    #[command(hide = true)]
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+
+    /// Print the man page in roff format.
+    #[command(hide = true)]
+    Man,
+}
+
+#[derive(Debug, Default, clap::Args)]
+struct RestartArgs {
+    /// Boot into the kernel loaded by kexec, skipping the firmware (systemd only).
+    #[arg(long)]
+    kexec: bool,
+
+    /// Boot into the setup of the firmware, i.e. the BIOS/UEFI setup (systemd only).
+    #[arg(long, conflicts_with = "kexec")]
+    firmware_setup: bool,
+}
+
+#[derive(Debug, Default, clap::Args)]
+struct LogoutArgs {
+    /// Logout the given user instead of $USER.
+    #[arg(long, value_parser = parse_user)]
+    user: Option<String>,
+
+    /// Logout every logged-in user, e.g. before a maintenance.
+    #[arg(long, conflicts_with = "user")]
+    user_all: bool,
+
+    /// Terminate the sessions of the given seat instead, e.g. seat1 on a multi-seat host.
+    #[arg(long, value_parser = parse_seat, conflicts_with_all = ["user", "user_all"])]
+    seat: Option<String>,
+
+    /// Check up to N times, --poll-interval apart, that the sessions of the user are closed after the logout.
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..), conflicts_with = "seat")]
+    count: Option<u32>,
+
+    /// Send SIGTERM to all processes of the user via loginctl kill-user, instead of asking the sessions to exit.
+    #[arg(long, conflicts_with = "seat")]
+    kill: bool,
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Command::Kill => write!(f, "Kill"),
+            Command::Halt => write!(f, "Halt"),
+            Command::Restart(RestartArgs { kexec: true, .. }) => write!(f, "Kexec"),
+            Command::Restart(_) => write!(f, "Restart"),
+            Command::Logout(_) => write!(f, "Logout"),
+            Command::Suspend => write!(f, "Suspend"),
+            Command::Hibernate => write!(f, "Hibernate"),
+            Command::HybridSleep => write!(f, "HybridSleep"),
+            Command::Lock => write!(f, "Lock"),
+            Command::Cancel => write!(f, "Cancel"),
+            Command::Status => write!(f, "Status"),
+            Command::Completions { .. } => write!(f, "Completions"),
+            Command::Man => write!(f, "Man"),
+            Command::Exec { .. } => write!(f, "Exec"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    FailedToWriteStdout(std::io::Error),
+    FailedToWriteFile(PathBuf, std::io::Error),
+    FailedToReadStdin(std::io::Error),
+    InvalidUserAnswer,
+    NoInput,
+    MissingEnv(String),
+    UnschedulableCommand(String),
+    UnverifiableCommand(String),
+    UnannounceableCommand(String),
+    UnverifiedRestart(Duration),
+    AuthorizationDismissed,
+    Interrupted,
+    FailedToReadConfig(PathBuf, String),
+    FailedToListUsers(crate::Error),
+    FailedToNotify(crate::Error),
+    MismatchedConfirmation(String),
+    SystemdIsNotInit(crate::Error),
+    FailedToListSessions(crate::Error),
+    RemainingSessions(usize),
+    InvalidReason(String),
+    UnsafeExec(String),
+    UnsupportedFirmwareSetup,
+    Aborted(String),
+    FailedToListInhibitors(crate::Error),
+    UnsupportedCommand(String),
+}
+
+impl std::error::Error for Error {}
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::FailedToWriteStdout(err) => {
+                write!(f, "failed to write to stdout: {}", err)
+            }
+            Error::FailedToWriteFile(path, err) => {
+                write!(f, "failed to write to {}: {}", path.display(), err)?;
+
+                match err.kind() {
+                    std::io::ErrorKind::PermissionDenied => {
+                        write!(
+                            f,
+                            " (the file or its directory is not writable by the current user)"
+                        )
+                    }
+                    std::io::ErrorKind::NotFound => {
+                        write!(f, " (the directory of the file does not exist)")
+                    }
+                    _ => Ok(()),
+                }
+            }
+            Error::FailedToReadStdin(err) => {
+                write!(f, "failed to read stdin: {}", err)
+            }
+            Error::InvalidUserAnswer => {
+                write!(f, "the given command does not exist")
+            }
+            Error::NoInput => {
+                write!(f, "no input received (is stdin a terminal?)")
+            }
+            Error::MissingEnv(var) => {
+                write!(f, "${var} should be set for this command")
+            }
+            Error::UnschedulableCommand(cmd) => {
+                write!(
+                    f,
+                    "{cmd} cannot be scheduled, only Kill, Halt and Restart can"
+                )
+            }
+            Error::UnverifiableCommand(cmd) => {
+                write!(
+                    f,
+                    "{cmd} cannot be verified, only an immediate Restart without the echo backend can"
+                )
+            }
+            Error::UnannounceableCommand(cmd) => {
+                write!(
+                    f,
+                    "{cmd} cannot be given a reason, only Kill, Halt and Restart can"
+                )
+            }
+            Error::UnverifiedRestart(timeout) => {
+                write!(f, "the restart did not take effect within {:?}", timeout)
+            }
+            Error::AuthorizationDismissed => {
+                write!(f, "the pkexec authorization dialog was dismissed")
+            }
+            Error::Interrupted => {
+                write!(f, "interrupted by the user")
+            }
+            Error::FailedToReadConfig(path, err) => {
+                write!(f, "failed to read the config {}: {}", path.display(), err)
+            }
+            Error::FailedToListUsers(err) => {
+                write!(f, "failed to list the logged-in users: {}", err)
+            }
+            Error::FailedToNotify(err) => {
+                write!(f, "failed to notify the users: {}", err)
+            }
+            Error::MismatchedConfirmation(expected) => {
+                write!(f, "the typed text does not match {:?}, aborted", expected)
+            }
+            Error::SystemdIsNotInit(err) => {
+                write!(
+                    f,
+                    "systemd is not the init of this host, e.g. in a container, try again with --backend sysv to use shutdown instead: {}",
+                    err
+                )
+            }
+            Error::FailedToListSessions(err) => {
+                write!(f, "failed to list the sessions: {}", err)
+            }
+            Error::RemainingSessions(count) => {
+                write!(f, "{count} session(s) still remain after the logout")
+            }
+            Error::InvalidReason(err) => {
+                write!(f, "invalid reason: {}", err)
+            }
+            Error::UnsupportedFirmwareSetup => {
+                write!(
+                    f,
+                    "--firmware-setup requires systemctl, i.e. the systemd backend without --after or --reason"
+                )
+            }
+            Error::UnsafeExec(program) => {
+                write!(
+                    f,
+                    "exec runs arbitrary commands, try again with --unsafe-exec to run {program}"
+                )
+            }
+            Error::Aborted(cmd) => {
+                write!(f, "{cmd} is aborted by the user")
+            }
+            Error::FailedToListInhibitors(err) => {
+                write!(f, "failed to list the inhibitors: {}", err)
+            }
+            Error::UnsupportedCommand(cmd) => {
+                write!(
+                    f,
+                    "{cmd} requires systemd, it is not supported on this platform"
+                )
+            }
+        }
+    }
+}
+
+/// Returns the exit code of `hpm` on success, i.e. zero unless `--propagate-exit-code` is set.
+fn try_run(
+    mut args: Args,
+    config: Config,
+    runner: &mut impl Runner,
+) -> Result<u8, Box<dyn std::error::Error>> {
+    env_logger::Builder::new()
+        .filter_level(match args.verbose {
+            0 => log::LevelFilter::Warn,
+            1 => log::LevelFilter::Info,
+            2 => log::LevelFilter::Debug,
+            _ => log::LevelFilter::Trace,
+        })
+        // The logger may be set already when hpm is embedded.
+        .try_init()
+        .ok();
+
+    #[cfg(all(feature = "signals", unix))]
+    if let Err(err) = crate::process::forward_signals() {
+        log::warn!("failed to forward the signals: {err}");
+    }
+
+    if args.interactive && args.command.is_some() {
+        Args::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "a subcommand cannot be used with '--interactive'",
+            )
+            .exit();
+    }
+
+    if args.interactive && args.assume_yes {
+        Args::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "'--assume-yes' cannot be used with '--interactive'",
+            )
+            .exit();
+    }
+
+    if args.list && (args.interactive || args.command.is_some()) {
+        Args::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "'--list' cannot be used with '--interactive' or a subcommand",
+            )
+            .exit();
+    }
+
+    if args.backend_info && (args.interactive || args.list || args.command.is_some()) {
+        Args::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "'--backend-info' cannot be used with '--interactive', '--list' or a subcommand",
+            )
+            .exit();
+    }
+
+    if args.dump_config && (args.interactive || args.list || args.command.is_some()) {
+        Args::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "'--dump-config' cannot be used with '--interactive', '--list' or a subcommand",
+            )
+            .exit();
+    }
+
+    if args.poll_interval > args.poll_timeout {
+        Args::command()
+            .error(
+                ErrorKind::ValueValidation,
+                "'--poll-interval' cannot be longer than '--poll-timeout'",
+            )
+            .exit();
+    }
+
+    config.apply(&mut args);
+
+    if args.list {
+        list(&args)?;
+        return Ok(0);
+    }
+
+    if args.backend_info {
+        print_backend_info(&args)?;
+        return Ok(0);
+    }
+
+    if args.dump_config {
+        dump_config(&args)?;
+        return Ok(0);
+    }
+
+    let (cmd, mut process) = if args.interactive {
+        interactive(&mut args)?
+    } else if let Some(cmd) = args.command.take() {
+        match cmd {
+            Command::Completions { shell } => {
+                clap_complete::generate(
+                    shell,
+                    &mut Args::command(),
+                    PROGRAM,
+                    &mut std::io::stdout(),
+                );
+                return Ok(0);
+            }
+            Command::Man => {
+                clap_mangen::Man::new(Args::command())
+                    .render(&mut std::io::stdout())
+                    .map_err(Error::FailedToWriteStdout)?;
+                return Ok(0);
+            }
+            _ => {}
+        }
+
+        let process = build_with(&cmd, &args, runner)?;
+        (cmd, process)
+    } else {
+        return Ok(0);
+    };
+
+    // The echo backend does not restart anything, so the restart would never take effect.
+    if args.verify
+        && (!matches!(cmd, Command::Restart(_))
+            || args.after.is_some()
+            || args.backend.unwrap_or_else(init_system) == Backend::Echo)
+    {
+        return Err(Error::UnverifiableCommand(cmd.to_string()).into());
+    }
+
+    if args.dry_run {
+        if !args.quiet {
+            println!("{}", process.dry_run());
+        }
+        return Ok(0);
+    }
+
+    // A testing aid for the scripts that call hpm, nothing runs and the outcome is a success.
+    if args.pretend_success {
+        let pretended_stdout = match args.format {
+            OutputFormat::Text => format!("{}\n", process.dry_run()),
+            OutputFormat::Json => String::new(),
+        };
+        print_outcome(&cmd, &process, pretended_stdout.as_bytes(), 0, &args)?;
+        return Ok(0);
+    }
+
+    // The status only reads the state of the host, so it needs no confirmation.
+    if let Command::Status = cmd {
+        let uptime = runner.run(&mut process)?;
+        print_outcome(&cmd, &process, status(&uptime).as_bytes(), 0, &args)?;
+        return Ok(0);
+    }
+
+    if args.idempotent
+        && matches!(cmd, Command::Kill | Command::Halt | Command::Restart(_))
+        && let Some((mode, at)) = scheduled_shutdown()
+    {
+        let at = humantime::format_rfc3339_seconds(at);
+
+        match args.format {
+            OutputFormat::Text if !args.quiet => println!("already scheduled: {mode} at {at}"),
+            OutputFormat::Text => {}
+            OutputFormat::Json => println!(
+                "{}",
+                serde_json::json!({
+                    "command": cmd.to_string(),
+                    "already_scheduled": true,
+                    "scheduled": format!("{mode} at {at}"),
+                    "success": true,
+                })
+            ),
+        }
+        return Ok(0);
+    }
+
+    if !confirm(&cmd, args.assume_yes, args.confirm_typing)? {
+        return Err(Error::Aborted(cmd.to_string()).into());
+    }
+
+    if !args.force && blocked_by_systemd(&cmd, &args) {
+        // The inhibitors are listed at best effort, systemd still enforces them unless --strict is set.
+        let inhibitors = match list_inhibitors(runner).map_err(Error::FailedToListInhibitors) {
+            Ok(inhibitors) => inhibitors,
+            Err(err) if args.strict => return Err(err.into()),
+            Err(err) => {
+                log::warn!("{err}");
+                Vec::new()
+            }
+        };
+
+        if !inhibitors.is_empty() {
+            eprintln!("{cmd} is blocked by:");
+            for inhibitor in &inhibitors {
+                eprintln!("  {} ({})", inhibitor.who, inhibitor.why);
+            }
+
+            // --assume-yes skips the confirmation, not the inhibitors, so systemd decides.
+            if !args.assume_yes {
+                if !ask("Ignore the inhibitors?", &mut std::io::stdin().lock())? {
+                    return Err(Error::Aborted(cmd.to_string()).into());
+                }
+                process.arg("--ignore-inhibitors");
+            }
+        }
+    }
+
+    if let Some(message) = &args.notify {
+        let mut wall = notify(message, args.backend);
+
+        // The users are notified at best effort, the command runs regardless unless --strict is set.
+        match runner.run(&mut wall).map_err(Error::FailedToNotify) {
+            Ok(_) => std::thread::sleep(args.notify_delay),
+            Err(err) if args.strict => return Err(err.into()),
+            Err(err) => log::warn!("{err}"),
+        }
+    }
+
+    if args.verify {
+        verify_restart(&mut process, Poll::new(&args), args.quiet, runner)?;
+        return Ok(0);
+    }
+
+    let result = runner.run(&mut process);
+
+    if args.print_exit_code {
+        match &result {
+            Ok(_) => eprintln!("exit: 0"),
+            Err(crate::Error::Exec { code, .. }) => eprintln!("exit: {code}"),
+            Err(_) => {}
+        }
+    }
+
+    let (process_stdout, ecode) = match result {
+        Err(crate::Error::Exec {
+            code: PKEXEC_DISMISSED_ECODE,
+            ..
+        }) if args.pkexec => {
+            return Err(Error::AuthorizationDismissed.into());
+        }
+        Err(err @ crate::Error::Exec { .. })
+            if args.backend.unwrap_or_else(init_system) == Backend::Systemd
+                && systemd_unreachable(&err) =>
+        {
+            return Err(Error::SystemdIsNotInit(err).into());
+        }
+        // A command succeeds only when it exits with zero, unless its exit code is propagated as is.
+        // Its stdout is printed as the outcome, and its stderr as is.
+        Err(crate::Error::Exec {
+            code,
+            stdout,
+            stderr,
+            ..
+        }) if args.propagate_exit_code => {
+            eprint!("{}", String::from_utf8_lossy(&stderr));
+            (stdout, code)
+        }
+        result => (result?, 0),
+    };
+
+    print_outcome(&cmd, &process, &process_stdout, ecode, &args)?;
+
+    if let Command::Logout(logout_args) = &cmd
+        && let Some(count) = logout_args.count
+    {
+        verify_logout(logout_args, &args, count, Poll::new(&args), runner)?;
+    }
+
+    // The exit codes that do not fit are reported as 255, the same as crate::Error::code.
+    match args.propagate_exit_code {
+        true => Ok(u8::try_from(ecode).unwrap_or(u8::MAX)),
+        false => Ok(0),
+    }
+}
+
+fn print_outcome(
+    cmd: &Command,
+    process: &Process,
+    process_stdout: &[u8],
+    ecode: i32,
+    args: &Args,
+) -> Result<(), Error> {
+    match args.format {
+        OutputFormat::Text if let Some(path) = &args.output_file => {
+            write_file(path, process_stdout)?
+        }
+        OutputFormat::Text if !args.quiet => std::io::stdout()
+            .write_all(process_stdout)
+            .map_err(Error::FailedToWriteStdout)?,
+        OutputFormat::Text => {}
+        OutputFormat::Json => println!(
+            "{}",
+            serde_json::json!({
+                "command": cmd.to_string(),
+                "program": process.program().to_string_lossy(),
+                "args": process
+                    .get_args()
+                    .map(|arg| arg.to_string_lossy())
+                    .collect::<Vec<_>>(),
+                "exit_code": ecode,
+                "stdout": String::from_utf8_lossy(process_stdout),
+                "success": ecode == 0,
+            })
+        ),
+    }
+
+    Ok(())
+}
+
+fn write_file(path: &Path, content: &[u8]) -> Result<(), Error> {
+    std::fs::write(path, content).map_err(|err| Error::FailedToWriteFile(path.to_owned(), err))
+}
+
+fn build(cmd: &Command, args: &Args) -> Result<Process, Error> {
+    build_with(cmd, args, &mut |process: &mut Process| process.exec())
+}
+
+/// Builds the command the same way as [`build`], except that the commands it depends on,
+/// e.g. the user listing of `logout --user-all`, are run via the given runner.
+fn build_with(cmd: &Command, args: &Args, runner: &mut impl Runner) -> Result<Process, Error> {
+    let backend = args.backend.unwrap_or_else(init_system);
+
+    // There is no systemctl or loginctl on macOS and Windows to run them, regardless of the backend.
+    if cfg!(any(target_os = "macos", target_os = "windows"))
+        && matches!(
+            cmd,
+            Command::Suspend
+                | Command::Hibernate
+                | Command::HybridSleep
+                | Command::Lock
+                | Command::Restart(RestartArgs { kexec: true, .. })
+        )
+    {
+        return Err(Error::UnsupportedCommand(cmd.to_string()));
+    }
+
+    if args.reason.is_some()
+        && !matches!(
+            cmd,
+            Command::Kill | Command::Halt | Command::Restart(RestartArgs { kexec: false, .. })
+        )
+    {
+        return Err(Error::UnannounceableCommand(cmd.to_string()));
+    }
+
+    // systemctl does not take a wall message, so a reason routes kill and restart to shutdown.
+    let power_backend = match args.reason {
+        Some(_) => Backend::Sysv,
+        None => backend,
+    };
+
+    let mut process = match (cmd, args.after) {
+        (Command::Completions { .. } | Command::Man, _) => {
+            unreachable!("{cmd} is handled by hpm itself")
+        }
+        (
+            Command::Kill | Command::Halt | Command::Restart(RestartArgs { kexec: false, .. }),
+            Some(after),
+        ) => schedule(cmd, after),
+        (_, Some(_)) => return Err(Error::UnschedulableCommand(cmd.to_string())),
+        (Command::Status, None) => Process::from_args("uptime", ["-p"]),
+        (Command::Kill, None) => kill(power_backend),
+        (Command::Halt, None) => halt(power_backend),
+        (Command::Restart(RestartArgs { kexec: true, .. }), None) => kexec(),
+        (Command::Restart(_), None) => restart(power_backend),
+        (Command::Logout(logout_args), None) => logout(backend, logout_args, runner)?,
+        (Command::Suspend, None) => suspend(),
+        (Command::Hibernate, None) => hibernate(),
+        (Command::HybridSleep, None) => hybrid_sleep(),
+        (Command::Lock, None) => lock(),
+        (Command::Cancel, None) => cancel(),
+        (Command::Exec { argv }, None) => exec(argv, args.unsafe_exec)?,
+    };
+
+    // The trailing arguments of shutdown are its wall message, see shutdown(8), whereas shutdown.exe takes a comment.
+    if let Some(reason) = &args.reason {
+        match cfg!(target_os = "windows") {
+            true => process.args(["/c", reason]),
+            false => process.arg(reason),
+        };
+    }
+
+    if let Command::Restart(RestartArgs {
+        firmware_setup: true,
+        ..
+    }) = cmd
+    {
+        // shutdown cannot reboot into the firmware, e.g. with --after, --reason or on hosts without systemd.
+        if process.program() != "systemctl" {
+            return Err(Error::UnsupportedFirmwareSetup);
+        }
+        process.arg("--firmware-setup");
+    }
+
+    if args.force
+        && process.program() == "systemctl"
+        && matches!(cmd, Command::Kill | Command::Halt | Command::Restart(_))
+    {
+        process.arg("--ignore-inhibitors");
+    }
+
+    // Only the power actions of systemctl broadcast the wall message, see systemctl(1).
+    if args.no_wall
+        && process.program() == "systemctl"
+        && matches!(cmd, Command::Kill | Command::Halt | Command::Restart(_))
+    {
+        process.arg("--no-wall");
+    }
+
+    if args.sudo {
+        process = process.with_sudo();
+    } else if args.pkexec {
+        process = process.with_pkexec();
+    }
+
+    // The password prompt of sudo needs the terminal, which is fine as long as the output is printed as is.
+    if args.sudo
+        && matches!(args.format, OutputFormat::Text)
+        && !args.quiet
+        && args.output_file.is_none()
+    {
+        process.inherit_stdio(true);
+    }
+
+    if backend == Backend::Echo {
+        process = echo(process);
+    }
+
+    Ok(process)
+}
+
+fn echo(process: Process) -> Process {
+    let mut echo = Process::new(std::process::Command::new("echo"));
+    echo.arg(process.program()).args(process.get_args());
+    echo
+}
+
+fn notify(message: &str, backend: Option<Backend>) -> Process {
+    let wall = Process::from_args("wall", [message]);
+
+    match backend {
+        Some(Backend::Echo) => echo(wall),
+        _ => wall,
+    }
+}
+
+/// Whether the command is a shutdown that systemd delays for its inhibitor locks.
+fn blocked_by_systemd(cmd: &Command, args: &Args) -> bool {
+    matches!(cmd, Command::Kill | Command::Halt | Command::Restart(_))
+        && args.after.is_none()
+        && args.reason.is_none()
+        && !cfg!(any(target_os = "macos", target_os = "windows"))
+        && args.backend.unwrap_or_else(init_system) == Backend::Systemd
+}
+
+/// An inhibitor lock of systemd-logind, see systemd-inhibit(1).
+#[derive(Debug, PartialEq)]
+struct Inhibitor {
+    who: String,
+    what: String,
+    why: String,
+    mode: String,
+}
+
+/// Lists the inhibitors that block a shutdown.
+fn list_inhibitors(runner: &mut impl Runner) -> Result<Vec<Inhibitor>, crate::Error> {
+    let mut process = Process::from_args("systemd-inhibit", ["--list", "--no-pager"]);
+    // The header is parsed, so it should not be translated.
+    process.env("LC_ALL", "C");
+
+    let stdout = runner.run(&mut process)?;
+
+    Ok(parse_inhibitors(&String::from_utf8_lossy(&stdout))
+        .into_iter()
+        .filter(|inhibitor| {
+            inhibitor.mode == "block" && inhibitor.what.split(':').any(|what| what == "shutdown")
+        })
+        .collect())
+}
+
+/// Parses the table of `systemd-inhibit --list`, whose columns are aligned to its header, e.g.
+///
+/// ```text
+/// WHO        UID  USER  PID  COMM   WHAT     WHY                MODE
+/// backup job 1000 alice 4242 restic shutdown Backup in progress block
+///
+/// 1 inhibitors listed.
+/// ```
+fn parse_inhibitors(content: &str) -> Vec<Inhibitor> {
+    let mut lines = content.lines();
+    let Some(header) = lines.find(|line| line.starts_with("WHO")) else {
+        return Vec::new();
+    };
+
+    // The values may contain spaces, hence the columns are sliced by the offsets of the header.
+    let offsets = header
+        .char_indices()
+        .filter(|(idx, ch)| !ch.is_whitespace() && (*idx == 0 || header[..*idx].ends_with(' ')))
+        .map(|(idx, _)| idx)
+        .collect::<Vec<_>>();
+    let column = |name: &str| {
+        offsets
+            .iter()
+            .position(|offset| header[*offset..].starts_with(name))
+    };
+    let (Some(who), Some(what), Some(why), Some(mode)) =
+        (column("WHO"), column("WHAT"), column("WHY"), column("MODE"))
+    else {
+        return Vec::new();
+    };
+
+    lines
+        .take_while(|line| !line.trim().is_empty())
+        .map(|line| {
+            let cell = |idx: usize| {
+                let start = offsets[idx].min(line.len());
+                let end = offsets
+                    .get(idx + 1)
+                    .map_or(line.len(), |end| (*end).min(line.len()));
+                line.get(start..end).unwrap_or_default().trim().to_owned()
+            };
+
+            Inhibitor {
+                who: cell(who),
+                what: cell(what),
+                why: cell(why),
+                mode: cell(mode),
+            }
+        })
+        .collect()
+}
+
+fn init_system() -> Backend {
+    if binary_exists("systemctl") && binary_exists("loginctl") {
+        Backend::Systemd
+    } else {
+        Backend::Sysv
+    }
+}
+
+/// Whether systemd is the init of the host, i.e. the same check as sd_booted(3).
+/// In some containers `systemctl` is installed even though systemd is not running.
+fn systemd_is_init() -> bool {
+    Path::new("/run/systemd/system").is_dir()
+}
+
+/// Whether systemctl or loginctl failed because systemd is unreachable, e.g. when systemd is not the init.
+/// The failures of the other programs (e.g. the ones of `exec`) are never attributed to systemd.
+fn systemd_unreachable(err: &crate::Error) -> bool {
+    match err {
+        crate::Error::Exec {
+            program, stderr, ..
+        } if Path::new(program)
+            .file_name()
+            .is_some_and(|name| name == "systemctl" || name == "loginctl") =>
+        {
+            !systemd_is_init()
+                || String::from_utf8_lossy(stderr).contains("Failed to connect to bus")
+        }
+        _ => false,
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+fn kill(backend: Backend) -> Process {
+    match backend {
+        Backend::Systemd | Backend::Echo => Process::from_args("systemctl", ["poweroff"]),
+        Backend::Sysv => Process::from_args("shutdown", ["-P", "now"]),
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+fn halt(backend: Backend) -> Process {
+    match backend {
+        Backend::Systemd | Backend::Echo => Process::from_args("systemctl", ["halt"]),
+        Backend::Sysv => Process::from_args("shutdown", ["-H", "now"]),
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+fn restart(backend: Backend) -> Process {
+    match backend {
+        Backend::Systemd | Backend::Echo => Process::from_args("systemctl", ["reboot"]),
+        Backend::Sysv => Process::from_args("shutdown", ["-r", "now"]),
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+fn logout(
+    backend: Backend,
+    logout_args: &LogoutArgs,
+    runner: &mut impl Runner,
+) -> Result<Process, Error> {
+    // Only logind knows the seats, regardless of the backend.
+    if let Some(seat) = &logout_args.seat {
+        return Ok(Process::from_args("loginctl", ["terminate-seat", seat]));
+    }
+
+    let mut process = match backend {
+        Backend::Systemd | Backend::Echo if logout_args.kill => {
+            Process::from_args("loginctl", ["kill-user"])
+        }
+        Backend::Systemd | Backend::Echo => Process::from_args("loginctl", ["terminate-user"]),
+        // pkill already kills the processes of the user, regardless of --kill.
+        Backend::Sysv => Process::from_args("pkill", ["-KILL", "-u"]),
+    };
+
+    if logout_args.user_all {
+        let users = list_users(backend, runner).map_err(Error::FailedToListUsers)?;
+
+        // pkill takes the users as a comma separated list, unlike loginctl.
+        match backend {
+            Backend::Systemd | Backend::Echo => process.args(users),
+            Backend::Sysv => process.arg(users.join(",")),
+        };
+
+        return Ok(process);
+    }
+
+    let user = match &logout_args.user {
+        Some(user) => user.to_owned(),
+        None => std::env::var("USER").map_err(|_| Error::MissingEnv("USER".into()))?,
+    };
+    process.arg(user);
+
+    Ok(process)
+}
+
+/// Builds the command of the exec subcommand as is, once it is allowed by `--unsafe-exec`.
+fn exec(argv: &[String], unsafe_exec: bool) -> Result<Process, Error> {
+    // clap requires at least one value, so the program is always there.
+    let (program, args) = argv.split_first().expect("the command should not be empty");
+
+    if !unsafe_exec {
+        return Err(Error::UnsafeExec(program.to_owned()));
+    }
+
+    Ok(Process::from_args(program, args))
+}
+
+/// Lists the logged-in users via `loginctl list-users`, or via `users` on hosts without systemd.
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+fn list_users(backend: Backend, runner: &mut impl Runner) -> Result<Vec<String>, crate::Error> {
+    let users = match backend {
+        // Nothing runs with the echo backend, so the listing is only echoed and no user is listed.
+        Backend::Echo => {
+            let process = Process::from_args("loginctl", ["list-users", "--no-legend"]);
+            runner.run(&mut echo(process))?;
+            Vec::new()
+        }
+        Backend::Systemd => {
+            let mut process = Process::from_args("loginctl", ["list-users", "--no-legend"]);
+            // The output is parsed, so it should not be translated.
+            process.env("LC_ALL", "C");
+
+            parse_users(&String::from_utf8_lossy(&runner.run(&mut process)?))
+                .into_iter()
+                .map(|user| user.name)
+                .collect()
+        }
+        Backend::Sysv => {
+            let stdout = runner.run(&mut Process::new(std::process::Command::new("users")))?;
+
+            let mut users = String::from_utf8_lossy(&stdout)
+                .split_whitespace()
+                .map(str::to_owned)
+                .collect::<Vec<_>>();
+            // users prints a user once per session.
+            users.sort();
+            users.dedup();
+            users
+        }
+    };
+
+    Ok(users)
+}
+
+/// A logged-in user, as listed by `loginctl list-users`.
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+#[derive(Debug, PartialEq)]
+struct User {
+    uid: u32,
+    name: String,
+}
+
+/// Parses the output of `loginctl list-users`, which holds a user per line along with its UID.
+/// The columns after the user vary by the version of systemd, e.g.
+///
+/// ```text
+/// 1000 alice no active
+/// 1001 bob   no online
+/// ```
+///
+/// The legend is skipped when it is printed regardless of `--no-legend`, e.g. by the older versions.
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+fn parse_users(content: &str) -> Vec<User> {
+    content
+        .lines()
+        .filter(|line| !line.trim_end().ends_with("listed."))
+        .filter_map(|line| {
+            let mut columns = line.split_whitespace();
+            let uid = columns.next()?.parse().ok()?;
+            let name = columns.next()?.to_owned();
+
+            Some(User { uid, name })
+        })
+        .collect()
+}
+
+/// Lists the sessions via `loginctl list-sessions`, or via `who` on hosts without systemd.
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+fn list_sessions(backend: Backend, runner: &mut impl Runner) -> Result<Vec<Session>, crate::Error> {
+    let sessions = match backend {
+        Backend::Systemd | Backend::Echo => {
+            let mut process = Process::from_args("loginctl", ["list-sessions", "--no-legend"]);
+            // The output is parsed, so it should not be translated.
+            process.env("LC_ALL", "C");
+
+            parse_sessions(&String::from_utf8_lossy(&runner.run(&mut process)?))
+        }
+        Backend::Sysv => {
+            let stdout = runner.run(&mut Process::new(std::process::Command::new("who")))?;
+
+            // who prints a session per line as its user and terminal, e.g. `alice tty2 ...`.
+            String::from_utf8_lossy(&stdout)
+                .lines()
+                .filter_map(|line| {
+                    let mut columns = line.split_whitespace();
+                    let user = columns.next()?.to_owned();
+                    let id = columns.next()?.to_owned();
+
+                    Some(Session { id, user })
+                })
+                .collect()
+        }
+    };
+
+    Ok(sessions)
+}
+
+/// A session of a logged-in user, as listed by `loginctl list-sessions` or `who`.
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+#[derive(Debug, PartialEq)]
+struct Session {
+    id: String,
+    user: String,
+}
+
+/// Parses the output of `loginctl list-sessions`, which holds a session per line along with the UID and the name of its user.
+/// The columns after the user vary by the version of systemd and the seat is empty for the remote sessions, e.g.
+///
+/// ```text
+///  2 1000 alice seat0 tty2 active no -
+/// 14 1001 bob         pts/1 active no -
+/// ```
+///
+/// The legend is skipped when it is printed regardless of `--no-legend`, the same as `parse_users`.
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+fn parse_sessions(content: &str) -> Vec<Session> {
+    content
+        .lines()
+        .filter(|line| !line.trim_end().ends_with("listed."))
+        .filter_map(|line| {
+            let mut columns = line.split_whitespace();
+            let id = columns.next()?.to_owned();
+            columns.next()?.parse::<u32>().ok()?;
+            let user = columns.next()?.to_owned();
+
+            Some(Session { id, user })
+        })
+        .collect()
+}
+
+#[cfg(target_os = "macos")]
+fn kill(_backend: Backend) -> Process {
+    Process::from_args("shutdown", ["-h", "now"])
+}
+
+// macOS has no separate halt, `shutdown -h` is the closest.
+#[cfg(target_os = "macos")]
+fn halt(_backend: Backend) -> Process {
+    Process::from_args("shutdown", ["-h", "now"])
+}
+
+#[cfg(target_os = "macos")]
+fn restart(_backend: Backend) -> Process {
+    Process::from_args("shutdown", ["-r", "now"])
+}
+
+#[cfg(target_os = "macos")]
+fn logout(
+    _backend: Backend,
+    _logout_args: &LogoutArgs,
+    _runner: &mut impl Runner,
+) -> Result<Process, Error> {
+    Ok(Process::from_args(
+        "osascript",
+        ["-e", r#"tell application "System Events" to log out"#],
+    ))
+}
+
+#[cfg(target_os = "windows")]
+fn kill(_backend: Backend) -> Process {
+    Process::from_args("shutdown", ["/s", "/t", "0"])
+}
+
+// Windows has no separate halt, powering off is the closest.
+#[cfg(target_os = "windows")]
+fn halt(_backend: Backend) -> Process {
+    Process::from_args("shutdown", ["/s", "/t", "0"])
+}
+
+#[cfg(target_os = "windows")]
+fn restart(_backend: Backend) -> Process {
+    Process::from_args("shutdown", ["/r", "/t", "0"])
+}
+
+// shutdown /l logs off the current user only.
+#[cfg(target_os = "windows")]
+fn logout(
+    _backend: Backend,
+    _logout_args: &LogoutArgs,
+    _runner: &mut impl Runner,
+) -> Result<Process, Error> {
+    Ok(Process::from_args("shutdown", ["/l"]))
+}
+
+// Only systemd loads the kernel and runs the kexec, regardless of the backend.
+fn kexec() -> Process {
+    Process::from_args("systemctl", ["kexec"])
+}
+
+fn suspend() -> Process {
+    Process::from_args("systemctl", ["suspend"])
+}
+
+fn hibernate() -> Process {
+    Process::from_args("systemctl", ["hibernate"])
+}
+
+fn hybrid_sleep() -> Process {
+    Process::from_args("systemctl", ["hybrid-sleep"])
+}
+
+fn lock() -> Process {
+    Process::from_args("loginctl", ["lock-sessions"])
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+fn cancel() -> Process {
+    Process::from_args("shutdown", ["-c"])
+}
+
+// The shutdown of macOS cannot cancel itself, it is cancelled by killing the waiting shutdown instead.
+#[cfg(target_os = "macos")]
+fn cancel() -> Process {
+    Process::from_args("killall", ["shutdown"])
+}
+
+#[cfg(target_os = "windows")]
+fn cancel() -> Process {
+    Process::from_args("shutdown", ["/a"])
+}
+
+/// Describes the scheduled shutdown along with the given output of `uptime -p`.
+fn status(uptime: &[u8]) -> String {
+    let mut status = match scheduled_shutdown() {
+        Some((mode, at)) => format!(
+            "Scheduled {} at {}\n",
+            mode,
+            humantime::format_rfc3339_seconds(at)
+        ),
+        None => String::from("No shutdown scheduled\n"),
+    };
+
+    status.push_str(&String::from_utf8_lossy(uptime));
+    status
+}
+
+fn scheduled_shutdown() -> Option<(String, SystemTime)> {
+    std::fs::read_to_string(SCHEDULED_SHUTDOWN_PATH)
+        .ok()
+        .and_then(|content| parse_scheduled_shutdown(&content))
+}
+
+/// Parses the scheduled shutdown file of systemd-logind,
+/// which holds the time (`USEC`) and the kind (`MODE`) of the shutdown, e.g.
+///
+/// ```text
+/// USEC=1700000000000000
+/// WARN_WALL=1
+/// MODE=reboot
+/// ```
+fn parse_scheduled_shutdown(content: &str) -> Option<(String, SystemTime)> {
+    let mut usec = None;
+    let mut mode = None;
+
+    for line in content.lines() {
+        match line.split_once('=') {
+            Some(("USEC", val)) => usec = val.parse::<u64>().ok(),
+            Some(("MODE", val)) => mode = Some(val.to_owned()),
+            _ => {}
+        }
+    }
+
+    let at = SystemTime::UNIX_EPOCH + Duration::from_micros(usec?);
+    Some((mode.unwrap_or_else(|| "shutdown".into()), at))
+}
+
+/// The polling settings of the helpers that wait for a command to take effect, e.g. [`verify_restart`].
+#[derive(Debug, Clone, Copy)]
+struct Poll {
+    interval: Duration,
+    timeout: Duration,
+}
+
+impl Poll {
+    fn new(args: &Args) -> Self {
+        Self {
+            interval: args.poll_interval,
+            timeout: args.poll_timeout,
+        }
+    }
+}
+
+/// Spawns the restart detached, then waits until the host is stopping or has already rebooted.
+/// The state of the host is probed via the given runner.
+fn verify_restart(
+    process: &mut Process,
+    poll: Poll,
+    quiet: bool,
+    runner: &mut impl Runner,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let boot_uptime = read_uptime();
+    process.spawn_detached()?;
+
+    let started = Instant::now();
+    while started.elapsed() < poll.timeout {
+        if restart_started(boot_uptime, runner) {
+            if !quiet {
+                eprintln!("the restart has started");
+            }
+            return Ok(());
+        }
+
+        // A restart that fails right away, e.g. when the authorization is denied, is not waited out.
+        process.try_wait_detached()?;
+
+        if !quiet {
+            eprintln!(
+                "waiting for the restart to take effect ({}s)",
+                started.elapsed().as_secs()
+            );
+        }
+        std::thread::sleep(poll.interval);
+    }
+
+    Err(Error::UnverifiedRestart(poll.timeout).into())
+}
+
+/// Counts the remaining sessions of the logged out user(s) up to `count` times, until none remain or the poll times out.
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+fn verify_logout(
+    logout_args: &LogoutArgs,
+    args: &Args,
+    count: u32,
+    poll: Poll,
+    runner: &mut impl Runner,
+) -> Result<(), Error> {
+    let backend = args.backend.unwrap_or_else(init_system);
+    // The echo backend does not log out anyone, so the sessions would always remain.
+    if backend == Backend::Echo {
+        return Ok(());
+    }
+
+    let user = match (&logout_args.user, logout_args.user_all) {
+        (_, true) => None,
+        (Some(user), false) => Some(user.to_owned()),
+        (None, false) => Some(std::env::var("USER").map_err(|_| Error::MissingEnv("USER".into()))?),
+    };
+
+    let started = Instant::now();
+    let mut remaining = 0;
+    for attempt in 1..=count {
+        remaining = list_sessions(backend, runner)
+            .map_err(Error::FailedToListSessions)?
+            .iter()
+            .filter(|session| user.as_ref().is_none_or(|user| &session.user == user))
+            .count();
+
+        if remaining == 0 {
+            return Ok(());
+        }
+
+        if !args.quiet {
+            eprintln!("{remaining} session(s) remain ({attempt}/{count})");
+        }
+        if attempt == count || started.elapsed() + poll.interval > poll.timeout {
+            break;
+        }
+        std::thread::sleep(poll.interval);
+    }
+
+    Err(Error::RemainingSessions(remaining))
+}
+
+// There is no session manager to ask on macOS and Windows.
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+fn verify_logout(
+    _logout_args: &LogoutArgs,
+    _args: &Args,
+    _count: u32,
+    _poll: Poll,
+    _runner: &mut impl Runner,
+) -> Result<(), Error> {
+    log::warn!("the logout cannot be verified on this platform");
+    Ok(())
+}
+
+fn restart_started(boot_uptime: Option<f64>, runner: &mut impl Runner) -> bool {
+    // is-system-running exits with non-zero unless the system is running, so the state is read either way.
+    let mut probe = Process::from_args("systemctl", ["is-system-running"]);
+    let stopping = match runner.run(&mut probe) {
+        Ok(stdout) | Err(crate::Error::Exec { stdout, .. }) => stdout.trim_ascii() == b"stopping",
+        Err(_) => false,
+    };
+
+    let rebooted = boot_uptime
+        .zip(read_uptime())
+        .is_some_and(|(before, now)| now < before);
+
+    stopping || rebooted
+}
+
+fn read_uptime() -> Option<f64> {
+    std::fs::read_to_string(UPTIME_PATH)
+        .ok()
+        .and_then(|content| parse_uptime(&content))
+}
+
+fn parse_uptime(content: &str) -> Option<f64> {
+    content.split_whitespace().next()?.parse().ok()
+}
+
+fn parse_user(s: &str) -> Result<String, String> {
+    if s.is_empty() {
+        return Err("the user should not be empty".into());
+    }
+
+    // Otherwise the user would be passed to loginctl as a flag.
+    if s.starts_with('-') {
+        return Err("the user should not start with '-'".into());
+    }
+
+    Ok(s.to_owned())
+}
+
+fn parse_seat(s: &str) -> Result<String, String> {
+    let valid = s.starts_with("seat")
+        && s.chars()
+            .all(|ch| ch.is_ascii_alphanumeric() || ch == '-' || ch == '_');
+
+    if !valid {
+        return Err("the seat should be alphanumeric and start with 'seat', e.g. seat0".into());
+    }
+
+    Ok(s.to_owned())
+}
+
+fn parse_message(s: &str) -> Result<String, String> {
+    if s.trim().is_empty() {
+        return Err("the message should not be empty".into());
+    }
+
+    // Otherwise the message would be passed to shutdown or wall as a flag.
+    if s.starts_with('-') {
+        return Err("the message should not start with '-'".into());
+    }
+
+    Ok(s.to_owned())
+}
+
+fn parse_delay(s: &str) -> Result<Duration, String> {
+    let delay = humantime::parse_duration(s).map_err(|err| err.to_string())?;
+
+    if delay.is_zero() {
+        return Err("the duration should be greater than zero".into());
+    }
+
+    Ok(delay)
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+fn schedule(cmd: &Command, after: Duration) -> Process {
+    let mode = match cmd {
+        Command::Restart(_) => "-r",
+        Command::Halt => "-H",
+        _ => "-P",
+    };
+
+    // `shutdown` only accepts whole minutes, round up so it never fires early.
+    let minutes = after.as_secs().div_ceil(60);
+    Process::from_args("shutdown", [mode.to_owned(), format!("+{minutes}")])
+}
+
+// macOS has no separate halt or power off flags, `shutdown -h` is the closest.
+#[cfg(target_os = "macos")]
+fn schedule(cmd: &Command, after: Duration) -> Process {
+    let mode = match cmd {
+        Command::Restart(_) => "-r",
+        _ => "-h",
+    };
+
+    // `shutdown` only accepts whole minutes, round up so it never fires early.
+    let minutes = after.as_secs().div_ceil(60);
+    Process::from_args("shutdown", [mode.to_owned(), format!("+{minutes}")])
+}
+
+// Windows has no separate halt, powering off is the closest.
+#[cfg(target_os = "windows")]
+fn schedule(cmd: &Command, after: Duration) -> Process {
+    let mode = match cmd {
+        Command::Restart(_) => "/r",
+        _ => "/s",
+    };
+
+    // shutdown.exe takes the delay in seconds.
+    Process::from_args(
+        "shutdown",
+        [mode.to_owned(), "/t".into(), after.as_secs().to_string()],
+    )
+}
+
+fn confirm(cmd: &Command, assume_yes: bool, confirm_typing: bool) -> Result<bool, Error> {
+    let expected = match cmd {
+        Command::Kill => "kill",
+        Command::Halt => "halt",
+        Command::Restart(_) => "restart",
+        _ => return Ok(true),
+    };
+
+    // The stricter confirmation wins, whatever turned on --assume-yes.
+    if confirm_typing {
+        return confirm_by_typing(expected, &mut std::io::stdin().lock()).map(|_| true);
+    }
+
+    if assume_yes {
+        return Ok(true);
+    }
+
+    ask(
+        &format!("Are you sure you want to {}?", cmd),
+        &mut std::io::stdin().lock(),
+    )
+}
+
+/// The stricter confirmation of `--confirm-typing`, the user has to type the name of the command to proceed.
+fn confirm_by_typing(expected: &str, input: &mut impl BufRead) -> Result<(), Error> {
+    let mut answer_buf = String::new();
+
+    eprint!("Type {:?} to confirm: ", expected);
+    let read_bytes = input
+        .read_line(&mut answer_buf)
+        .map_err(Error::FailedToReadStdin)?;
+
+    if read_bytes == 0 {
+        return Err(Error::NoInput);
+    }
+
+    match answer_buf.trim().eq_ignore_ascii_case(expected) {
+        true => Ok(()),
+        false => Err(Error::MismatchedConfirmation(expected.to_string())),
+    }
+}
+
+fn ask(question: &str, input: &mut impl BufRead) -> Result<bool, Error> {
+    let mut answer_buf = String::new();
+
+    eprint!("{} [y/N] ", question);
+    let read_bytes = input
+        .read_line(&mut answer_buf)
+        .map_err(Error::FailedToReadStdin)?;
+
+    if read_bytes == 0 {
+        return Err(Error::NoInput);
+    }
+
+    let answer = answer_buf.trim().to_lowercase();
+    Ok(answer == "y" || answer == "yes")
+}
+
+/// An option of the interactive mode, along with its pre-built [`Process`].
+struct Choice {
+    cmd: Command,
+    process: Result<Process, Error>,
+}
+
+impl Choice {
+    fn new(cmd: Command, args: &Args) -> Self {
+        let process = build(&cmd, args);
+        Self { cmd, process }
+    }
+
+    fn into_selection(self) -> Result<(Command, Process), Error> {
+        self.process.map(|process| (self.cmd, process))
+    }
+
+    fn style(&self) -> Style {
+        match self.cmd {
+            Command::Kill | Command::Halt => AnsiColor::Red.on_default(),
+            Command::Restart(_) => AnsiColor::Yellow.on_default(),
+            Command::Logout(_) => AnsiColor::Green.on_default(),
+            _ => Style::new(),
+        }
+    }
+}
+
+impl fmt::Display for Choice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.process {
+            Ok(process) => write!(f, "{} [{}]", self.cmd, process.command_line()),
+            Err(err) => write!(f, "{} [{}]", self.cmd, err),
+        }
+    }
+}
+
+const MENU: [Command; 9] = [
+    Command::Kill,
+    Command::Restart(RestartArgs {
+        kexec: false,
+        firmware_setup: false,
+    }),
+    Command::Logout(LogoutArgs {
+        user: None,
+        user_all: false,
+        seat: None,
+        count: None,
+        kill: false,
+    }),
+    Command::Suspend,
+    Command::Hibernate,
+    Command::HybridSleep,
+    Command::Lock,
+    Command::Cancel,
+    Command::Halt,
+];
+
+fn choices(args: &Args) -> Vec<Choice> {
+    MENU.into_iter().map(|cmd| Choice::new(cmd, args)).collect()
+}
+
+fn name_map() -> HashMap<String, u8> {
+    MENU.iter()
+        .enumerate()
+        .map(|(idx, cmd)| (cmd.to_string().to_lowercase(), idx as u8))
+        .collect()
+}
+
+fn parse_choice(value: &str) -> Result<u8, String> {
+    parse_answer(value, &name_map())
+        .filter(|idx| usize::from(*idx) < MENU.len())
+        .ok_or_else(|| {
+            let names = MENU.iter().map(|cmd| cmd.to_string()).collect::<Vec<_>>();
+            format!("expected an index or one of {}", names.join(", "))
+        })
+}
+
+/// Pairs each choice with its subcommand name, its description and the command line it resolves to.
+fn list_entries(args: &Args) -> Vec<(String, String, String)> {
+    let cli = Args::command();
+
+    choices(args)
+        .into_iter()
+        .map(|choice| {
+            let display_name = choice.cmd.to_string();
+            let subcommand = cli.get_subcommands().find(|subcommand| {
+                subcommand
+                    .get_name()
+                    .replace('-', "")
+                    .eq_ignore_ascii_case(&display_name)
+            });
+
+            let name = subcommand.map_or(display_name.clone(), |subcommand| {
+                subcommand.get_name().to_string()
+            });
+            let about = subcommand
+                .and_then(|subcommand| subcommand.get_about())
+                .map(|about| about.to_string())
+                .unwrap_or_default();
+            let command_line = match &choice.process {
+                Ok(process) => process.command_line(),
+                Err(err) => err.to_string(),
+            };
+
+            (name, about, command_line)
+        })
+        .collect()
+}
+
+fn list(args: &Args) -> Result<(), Error> {
+    let entries = list_entries(args);
+
+    match args.format {
+        OutputFormat::Text => {
+            let name_width = entries.iter().map(|(name, ..)| name.len()).max();
+            let about_width = entries.iter().map(|(_, about, _)| about.len()).max();
+            let (name_width, about_width) = (name_width.unwrap_or(0), about_width.unwrap_or(0));
+
+            let mut stdout = std::io::stdout().lock();
+            for (name, about, command_line) in entries {
+                writeln!(
+                    stdout,
+                    "{name:<name_width$}  {about:<about_width$}  {command_line}"
+                )
+                .map_err(Error::FailedToWriteStdout)?;
+            }
+        }
+        OutputFormat::Json => println!(
+            "{}",
+            serde_json::Value::from_iter(entries.into_iter().map(
+                |(name, about, command_line)| serde_json::json!({
+                    "command": name,
+                    "description": about,
+                    "command_line": command_line,
+                })
+            ))
+        ),
+    }
+
+    Ok(())
+}
+
+/// Pairs the backend, the target OS and the programs of the backends with their resolved paths.
+fn backend_info(args: &Args) -> Vec<(String, String)> {
+    let backend = match args.backend {
+        Some(backend) => format!("{backend:?} (given)"),
+        None => format!("{:?} (detected)", init_system()),
+    };
+
+    let mut entries = vec![
+        (String::from("backend"), backend.to_lowercase()),
+        (String::from("os"), std::env::consts::OS.to_string()),
+    ];
+
+    for program in ["systemctl", "loginctl", "shutdown"] {
+        let path = match which::which(program) {
+            Ok(path) => path.display().to_string(),
+            Err(_) => String::from("not found"),
+        };
+        entries.push((program.to_string(), path));
+    }
+
+    entries
+}
+
+fn print_backend_info(args: &Args) -> Result<(), Error> {
+    let entries = backend_info(args);
+
+    match args.format {
+        OutputFormat::Text => {
+            let key_width = entries
+                .iter()
+                .map(|(key, _)| key.len() + 1)
+                .max()
+                .unwrap_or(0);
+
+            let mut stdout = std::io::stdout().lock();
+            for (key, val) in entries {
+                writeln!(stdout, "{:<key_width$}  {val}", format!("{key}:"))
+                    .map_err(Error::FailedToWriteStdout)?;
+            }
+        }
+        OutputFormat::Json => println!(
+            "{}",
+            serde_json::Value::from_iter(
+                entries
+                    .into_iter()
+                    .map(|(key, val)| (key, serde_json::Value::from(val)))
+            )
+        ),
+    }
+
+    Ok(())
+}
+
+/// The settings that `hpm` runs with, after the flags, the environment and the config are merged.
+#[derive(Debug, Serialize)]
+struct EffectiveConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    config: Option<PathBuf>,
+    backend: String,
+    sudo: bool,
+    pkexec: bool,
+    no_wall: bool,
+    assume_yes: bool,
+    confirm_typing: bool,
+    force: bool,
+    strict: bool,
+    idempotent: bool,
+    default: String,
+    retries: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timeout: Option<String>,
+    poll_interval: String,
+    poll_timeout: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    after: Option<String>,
+    format: String,
+    color: String,
+}
+
+fn value_name(value: impl ValueEnum) -> String {
+    value
+        .to_possible_value()
+        .map(|value| value.get_name().to_string())
+        .unwrap_or_default()
+}
+
+impl EffectiveConfig {
+    fn new(args: &Args) -> Self {
+        let default = args.default.unwrap_or(DEFAULT_CHOICE);
+
+        Self {
+            config: Config::path().filter(|path| path.exists()),
+            backend: value_name(args.backend.unwrap_or_else(init_system)),
+            sudo: args.sudo,
+            pkexec: args.pkexec,
+            no_wall: args.no_wall,
+            assume_yes: args.assume_yes,
+            confirm_typing: args.confirm_typing,
+            force: args.force,
+            strict: args.strict,
+            idempotent: args.idempotent,
+            default: MENU[usize::from(default)].to_string().to_lowercase(),
+            retries: args.retries,
+            timeout: args
+                .timeout
+                .map(|timeout| humantime::format_duration(timeout).to_string()),
+            poll_interval: humantime::format_duration(args.poll_interval).to_string(),
+            poll_timeout: humantime::format_duration(args.poll_timeout).to_string(),
+            after: args
+                .after
+                .map(|after| humantime::format_duration(after).to_string()),
+            format: value_name(args.format),
+            color: value_name(args.color),
+        }
+    }
+}
+
+fn dump_config(args: &Args) -> Result<(), Error> {
+    let effective_config = EffectiveConfig::new(args);
+
+    let dump = match args.format {
+        OutputFormat::Text => toml::to_string(&effective_config).map_err(|err| err.to_string()),
+        OutputFormat::Json => serde_json::to_string(&effective_config)
+            .map(|json| json + "\n")
+            .map_err(|err| err.to_string()),
+    };
+
+    // The config is made of plain values, so serializing it cannot fail in practice.
+    let dump = dump.unwrap_or_else(|err| unreachable!("failed to serialize the config: {err}"));
+
+    std::io::stdout()
+        .write_all(dump.as_bytes())
+        .map_err(Error::FailedToWriteStdout)
+}
+
+fn interactive(args: &mut Args) -> Result<(Command, Process), Error> {
+    let (cmd, process) = select_choice(args)?;
+
+    if args.ask_reason
+        && matches!(
+            cmd,
+            Command::Kill | Command::Halt | Command::Restart(RestartArgs { kexec: false, .. })
+        )
+        && let Some(reason) = ask_reason(&mut std::io::stdin().lock())?
+    {
+        // The reason changes the command, e.g. systemctl poweroff becomes shutdown -P now.
+        args.reason = Some(reason);
+        let process = build(&cmd, args)?;
+        return Ok((cmd, process));
+    }
+
+    Ok((cmd, process))
+}
+
+fn select_choice(args: &Args) -> Result<(Command, Process), Error> {
+    let choices = choices(args);
+    let default = args.default.unwrap_or(DEFAULT_CHOICE);
+
+    // The menu needs a terminal, piped answers are still read by the numbered prompt.
+    #[cfg(feature = "tui")]
+    if std::io::stdin().is_terminal() && std::io::stderr().is_terminal() {
+        let color = args.color.enabled(&std::io::stderr());
+        return select(choices, default, color)?.into_selection();
+    }
+
+    let color = args.color.enabled(&std::io::stdout());
+    prompt(choices, default, color, &mut std::io::stdin().lock())?.into_selection()
+}
+
+/// Reads the reason of `--ask-reason`, an empty line means that there is no reason to send.
+fn ask_reason(input: &mut impl BufRead) -> Result<Option<String>, Error> {
+    let mut answer_buf = String::new();
+
+    eprint!("Reason (leave empty to skip): ");
+    let read_bytes = input
+        .read_line(&mut answer_buf)
+        .map_err(Error::FailedToReadStdin)?;
+
+    if read_bytes == 0 {
+        return Err(Error::NoInput);
+    }
+
+    match answer_buf.trim() {
+        "" => Ok(None),
+        reason => parse_message(reason)
+            .map(Some)
+            .map_err(Error::InvalidReason),
+    }
+}
+
+#[cfg(feature = "tui")]
+fn select(mut choices: Vec<Choice>, default: u8, color: bool) -> Result<Choice, Error> {
+    let items = choices
+        .iter()
+        .map(|choice| paint(choice, choice.style(), color));
+
+    let selected_idx = dialoguer::Select::new()
+        .with_prompt("Select the command you wish to execute")
+        .items(items)
+        .default(usize::from(default))
+        .interact_opt()
+        .map_err(|err| match err {
+            dialoguer::Error::IO(err) if err.kind() == std::io::ErrorKind::Interrupted => {
+                Error::Interrupted
+            }
+            dialoguer::Error::IO(err) => Error::FailedToReadStdin(err),
+        })?
+        .ok_or(Error::Interrupted)?;
+
+    Ok(choices.swap_remove(selected_idx))
+}
+
+fn prompt(
+    choices: Vec<Choice>,
+    default: u8,
+    color: bool,
+    input: &mut impl BufRead,
+) -> Result<Choice, Error> {
+    let mut prompt_str = String::new();
+    let mut choice_map: HashMap<u8, Choice> = HashMap::new();
+    let name_map = name_map();
+
+    for (idx, choice) in choices.into_iter().enumerate() {
+        let idx = idx as u8;
+        let painted = paint(&choice, choice.style(), color);
+        prompt_str = match idx == default {
+            true => format!("{}, ({}) {} [default]", prompt_str, idx, painted),
+            false => format!("{}, ({}) {}", prompt_str, idx, painted),
+        };
+        choice_map.insert(idx, choice);
+    }
+
+    let prompt_str = prompt_str.strip_prefix(", ").unwrap();
+
+    println!("Select the command you wish to execute:\n{}", prompt_str);
+
+    for attempt in 1..=INTERACTIVE_ATTEMPTS {
+        let mut answer_buf = String::new();
+        let read_bytes = input
+            .read_line(&mut answer_buf)
+            .map_err(Error::FailedToReadStdin)?;
+
+        // An empty line selects the default, whereas EOF means that no answer is coming.
+        if read_bytes == 0 {
+            return Err(Error::NoInput);
+        }
+
+        let choice_key = match answer_buf.trim() {
+            "" => Some(default),
+            answer => parse_answer(answer, &name_map),
+        };
+        let selected_choice = choice_key.and_then(|choice_key| choice_map.remove(&choice_key));
+
+        if let Some(selected_choice) = selected_choice {
+            return Ok(selected_choice);
+        }
+
+        if attempt < INTERACTIVE_ATTEMPTS {
+            println!("Invalid selection, try again");
+        }
+    }
+
+    Err(Error::InvalidUserAnswer)
+}
+
+/// Resolves the answer to a choice key, either by its index or by its case-insensitive name.
+/// The dashes are ignored, so both "HybridSleep" and "hybrid-sleep" are accepted.
+fn parse_answer(answer: &str, name_map: &HashMap<String, u8>) -> Option<u8> {
+    let answer = answer.trim();
+
+    answer.parse::<u8>().ok().or_else(|| {
+        name_map
+            .get(&answer.replace('-', "").to_lowercase())
+            .copied()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_have_valid_args() {
+        Args::command().debug_assert();
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    #[test]
+    fn should_reboot_into_the_firmware_setup() {
+        let args = Args::parse_from(["hpm", "--backend", "systemd", "restart", "--firmware-setup"]);
+        let cmd = args.command.as_ref().unwrap();
+
+        assert_eq!(cmd.to_string(), "Restart");
+        assert_eq!(
+            build(cmd, &args).unwrap().command_line(),
+            "systemctl reboot --firmware-setup"
+        );
+
+        let args = Args::parse_from(["hpm", "--backend", "sysv", "restart", "--firmware-setup"]);
+        let cmd = args.command.as_ref().unwrap();
+        assert!(matches!(
+            build(cmd, &args),
+            Err(Error::UnsupportedFirmwareSetup)
+        ));
+
+        assert!(Args::try_parse_from(["hpm", "restart", "--kexec", "--firmware-setup"]).is_err());
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    #[test]
+    fn should_skip_wall_message_of_systemctl_only() {
+        let args = Args::parse_from(["hpm", "--backend", "systemd", "--no-wall"]);
+
+        assert_eq!(
+            build(&Command::Restart(RestartArgs::default()), &args)
+                .unwrap()
+                .command_line(),
+            "systemctl reboot --no-wall"
+        );
+        assert_eq!(
+            build(&Command::Lock, &args).unwrap().command_line(),
+            "loginctl lock-sessions"
+        );
+
+        let logout_args = LogoutArgs {
+            user: Some("alice".into()),
+            user_all: false,
+            seat: None,
+            count: None,
+            kill: false,
+        };
+        assert_eq!(
+            build(&Command::Logout(logout_args), &args)
+                .unwrap()
+                .command_line(),
+            "loginctl terminate-user alice"
+        );
+        assert_eq!(
+            build(&Command::Suspend, &args).unwrap().command_line(),
+            "systemctl suspend"
+        );
+
+        let args = Args::parse_from([
+            "hpm",
+            "--backend",
+            "systemd",
+            "--no-wall",
+            "--unsafe-exec",
+            "exec",
+            "systemctl",
+            "poweroff",
+        ]);
+        let cmd = args.command.as_ref().unwrap();
+        assert_eq!(
+            build(cmd, &args).unwrap().command_line(),
+            "systemctl poweroff"
+        );
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    #[test]
+    fn should_kill_the_user_with_kill() {
+        let args = Args::parse_from([
+            "hpm",
+            "--backend",
+            "systemd",
+            "logout",
+            "--kill",
+            "--user",
+            "alice",
+        ]);
+        let Some(Command::Logout(logout_args)) = &args.command else {
+            panic!("logout should be parsed");
+        };
+
+        assert!(logout_args.kill);
+        assert_eq!(
+            logout(Backend::Systemd, logout_args, &mut MockRunner::default())
+                .unwrap()
+                .command_line(),
+            "loginctl kill-user alice"
+        );
+        assert_eq!(
+            logout(Backend::Sysv, logout_args, &mut MockRunner::default())
+                .unwrap()
+                .command_line(),
+            "pkill -KILL -u alice"
+        );
+        assert!(Args::try_parse_from(["hpm", "logout", "--kill", "--seat", "seat1"]).is_err());
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    #[test]
+    fn should_fall_back_to_sysv_commands() {
+        assert_eq!(kill(Backend::Sysv).command_line(), "shutdown -P now");
+        assert_eq!(restart(Backend::Sysv).command_line(), "shutdown -r now");
+        assert_eq!(halt(Backend::Sysv).command_line(), "shutdown -H now");
+    }
+
+    #[test]
+    fn should_restart_with_kexec() {
+        let mut args = Args::parse_from(["hpm", "--backend", "systemd", "restart", "--kexec"]);
+        let cmd = args.command.take().unwrap();
+
+        assert_eq!(
+            build(&cmd, &args).unwrap().command_line(),
+            "systemctl kexec"
+        );
+
+        args.after = Some(Duration::from_secs(60));
+        assert!(matches!(
+            build(&cmd, &args),
+            Err(Error::UnschedulableCommand(_))
+        ));
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    #[test]
+    fn should_parse_the_logged_in_users() {
+        let user = |uid, name: &str| User {
+            uid,
+            name: name.into(),
+        };
+
+        // systemd 256 and later.
+        assert_eq!(
+            parse_users("1000 alice no active\n1001 bob   no online\n"),
+            [user(1000, "alice"), user(1001, "bob")]
+        );
+        // systemd 245, along with its legend.
+        assert_eq!(
+            parse_users("      UID USER\n     1000 alice\n\n1 users listed.\n"),
+            [user(1000, "alice")]
+        );
+        assert!(parse_users("").is_empty());
+
+        assert!(Args::try_parse_from(["hpm", "logout", "--user", "alice", "--user-all"]).is_err());
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    #[test]
+    fn should_parse_the_sessions() {
+        let session = |id: &str, user: &str| Session {
+            id: id.into(),
+            user: user.into(),
+        };
+
+        // The remote sessions have no seat.
+        assert_eq!(
+            parse_sessions(
+                " 2 1000 alice seat0 tty2 active no -\n14 1001 bob         pts/1 active no -\n"
+            ),
+            [session("2", "alice"), session("14", "bob")]
+        );
+        // systemd 245, along with its legend.
+        assert_eq!(
+            parse_sessions(
+                "SESSION  UID USER  SEAT  TTY\n      2 1000 alice seat0 tty2\n\n1 sessions listed.\n"
+            ),
+            [session("2", "alice")]
+        );
+        assert!(parse_sessions("").is_empty());
+
+        assert!(Args::try_parse_from(["hpm", "logout", "--count", "0"]).is_err());
+        assert!(
+            Args::try_parse_from(["hpm", "logout", "--seat", "seat1", "--count", "3"]).is_err()
+        );
+    }
+
+    #[test]
+    fn should_parse_the_inhibitors() {
+        let inhibitors = parse_inhibitors(concat!(
+            "WHO            UID  USER  PID  COMM           WHAT     WHY                                       MODE\n",
+            "NetworkManager 0    root  1010 NetworkManager sleep    NetworkManager needs to turn off networks delay\n",
+            "backup job     1000 alice 4242 restic         shutdown Backup in progress                        block\n",
+            "\n",
+            "2 inhibitors listed.\n",
+        ));
+
+        assert_eq!(inhibitors.len(), 2);
+        assert_eq!(
+            inhibitors[1],
+            Inhibitor {
+                who: "backup job".into(),
+                what: "shutdown".into(),
+                why: "Backup in progress".into(),
+                mode: "block".into(),
+            }
+        );
+        assert!(parse_inhibitors("0 inhibitors listed.\n").is_empty());
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    #[test]
+    fn should_ignore_inhibitors_with_force() {
+        let args = Args::parse_from(["hpm", "--backend", "systemd", "--force", "-y"]);
+
+        assert_eq!(
+            build(&Command::Kill, &args).unwrap().command_line(),
+            "systemctl poweroff --ignore-inhibitors"
+        );
+        assert_eq!(
+            build(&Command::Suspend, &args).unwrap().command_line(),
+            "systemctl suspend"
+        );
+
+        let args = Args::parse_from(["hpm", "--backend", "sysv", "--force"]);
+        assert_eq!(
+            build(&Command::Kill, &args).unwrap().command_line(),
+            "shutdown -P now"
+        );
+    }
+
+    #[cfg(target_os = "windows")]
+    #[test]
+    fn should_build_windows_commands() {
+        assert_eq!(kill(Backend::Sysv).command_line(), "shutdown /s /t 0");
+        assert_eq!(restart(Backend::Sysv).command_line(), "shutdown /r /t 0");
+        assert_eq!(cancel().command_line(), "shutdown /a");
+
+        let after = parse_delay("1h30s").unwrap();
+        assert_eq!(
+            schedule(&Command::Restart(RestartArgs::default()), after).command_line(),
+            "shutdown /r /t 3630"
+        );
+        assert_eq!(
+            schedule(&Command::Halt, after).command_line(),
+            "shutdown /s /t 3630"
+        );
+
+        let args = Args::parse_from(["hpm", "--after", "5m", "--reason", "kernel upgrade"]);
+        assert_eq!(
+            build(&Command::Kill, &args).unwrap().command_line(),
+            "shutdown /s /t 300 /c kernel upgrade"
+        );
+        assert!(matches!(
+            build(&Command::Suspend, &args),
+            Err(Error::UnsupportedCommand(_))
+        ));
+    }
+
+    #[cfg(target_os = "macos")]
+    #[test]
+    fn should_build_macos_commands() {
+        assert_eq!(cancel().command_line(), "killall shutdown");
+
+        let after = parse_delay("1h30s").unwrap();
+        assert_eq!(
+            schedule(&Command::Restart(RestartArgs::default()), after).command_line(),
+            "shutdown -r +61"
+        );
+        assert_eq!(
+            schedule(&Command::Kill, after).command_line(),
+            "shutdown -h +61"
+        );
+
+        let args = Args::parse_from(["hpm", "--reason", "kernel upgrade"]);
+        assert_eq!(
+            build(&Command::Halt, &args).unwrap().command_line(),
+            "shutdown -h now kernel upgrade"
+        );
+        assert!(matches!(
+            build(&Command::Lock, &args),
+            Err(Error::UnsupportedCommand(_))
+        ));
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    #[test]
+    fn should_terminate_the_given_seat() {
+        let mut args = Args::parse_from(["hpm", "--backend", "sysv", "logout", "--seat", "seat1"]);
+        let cmd = args.command.take().unwrap();
+
+        assert_eq!(
+            build(&cmd, &args).unwrap().command_line(),
+            "loginctl terminate-seat seat1"
+        );
+
+        assert!(parse_seat("seat-1").is_ok());
+        assert!(parse_seat("1").is_err());
+        assert!(parse_seat("seat 1").is_err());
+        assert!(Args::try_parse_from(["hpm", "logout", "--seat", "seat1", "--user-all"]).is_err());
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    #[test]
+    fn should_echo_commands_with_echo_backend() {
+        let args = Args::parse_from(["hpm", "--backend", "echo", "--sudo"]);
+
+        assert_eq!(
+            build(&Command::Kill, &args).unwrap().command_line(),
+            "echo sudo systemctl poweroff"
+        );
+    }
+
+    #[test]
+    fn should_let_flags_override_config() {
+        let config: Config = toml::from_str("sudo = true\nno_wall = true").unwrap();
+        let mut args = Args::parse_from(["hpm", "--pkexec", "kill"]);
+
+        config.apply(&mut args);
+
+        assert!(!args.sudo && args.pkexec && args.no_wall && !args.assume_yes);
+
+        let config: Config = toml::from_str("assume_yes = true").unwrap();
+        let mut args = Args::parse_from(["hpm", "--confirm-typing", "kill"]);
+
+        config.apply(&mut args);
+
+        assert!(args.confirm_typing && !args.assume_yes);
+
+        let config: Config = toml::from_str("assume_yes = true").unwrap();
+        let mut args = Args::parse_from(["hpm", "--interactive"]);
+
+        config.apply(&mut args);
+
+        assert!(args.interactive && !args.assume_yes);
+    }
+
+    #[test]
+    fn should_reject_unknown_config_keys() {
+        assert!(toml::from_str::<Config>("sudoo = true").is_err());
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    #[test]
+    fn should_schedule_in_whole_minutes() {
+        let after = parse_delay("1h30s").unwrap();
+
+        assert_eq!(
+            schedule(&Command::Restart(RestartArgs::default()), after).command_line(),
+            "shutdown -r +61"
+        );
+        assert_eq!(
+            schedule(&Command::Kill, after).command_line(),
+            "shutdown -P +61"
+        );
+        assert_eq!(
+            schedule(&Command::Halt, after).command_line(),
+            "shutdown -H +61"
+        );
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    #[test]
+    fn should_send_the_reason_as_the_wall_message_of_shutdown() {
+        let args = Args::parse_from(["hpm", "--backend", "systemd", "--reason", "kernel upgrade"]);
+
+        assert_eq!(
+            build(&Command::Restart(RestartArgs::default()), &args)
+                .unwrap()
+                .command_line(),
+            "shutdown -r now kernel upgrade"
+        );
+        assert!(matches!(
+            build(&Command::Suspend, &args),
+            Err(Error::UnannounceableCommand(_))
+        ));
+
+        let args = Args::parse_from(["hpm", "--after", "5m", "--reason", "kernel upgrade"]);
+        assert_eq!(
+            build(&Command::Kill, &args).unwrap().command_line(),
+            "shutdown -P +5 kernel upgrade"
+        );
+    }
+
+    #[test]
+    fn should_reject_empty_messages() {
+        assert!(parse_message("kernel upgrade").is_ok());
+        assert!(parse_message(" ").is_err());
+        assert!(parse_message("-k").is_err());
+    }
+
+    #[test]
+    fn should_parse_scheduled_shutdown() {
+        let scheduled =
+            parse_scheduled_shutdown("USEC=1700000000000000\nWARN_WALL=1\nMODE=reboot\n");
+
+        assert_eq!(
+            scheduled,
+            Some((
+                String::from("reboot"),
+                SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000)
+            ))
+        );
+        assert_eq!(parse_scheduled_shutdown("MODE=reboot\n"), None);
+    }
+
+    #[test]
+    fn should_reject_flag_like_users() {
+        assert!(parse_user("alice").is_ok());
+        assert!(parse_user("").is_err());
+        assert!(parse_user("--help").is_err());
+    }
+
+    #[test]
+    fn should_reject_zero_delay() {
+        assert!(parse_delay("0s").is_err());
+    }
+
+    #[test]
+    fn should_list_the_subcommands_with_their_command_lines() {
+        let args = Args::parse_from(["hpm", "--backend", "echo", "--list"]);
+        let entries = list_entries(&args);
+
+        assert_eq!(entries.len(), 9);
+        assert!(entries.contains(&(
+            "hybrid-sleep".into(),
+            "Hibernate and suspend the system".into(),
+            "echo systemctl hybrid-sleep".into()
+        )));
+    }
+
+    #[test]
+    fn should_report_the_given_backend() {
+        let args = Args::parse_from(["hpm", "--backend", "sysv", "--backend-info"]);
+        let entries = backend_info(&args);
+
+        assert_eq!(entries[0], ("backend".into(), "sysv (given)".into()));
+        assert_eq!(entries[1], ("os".into(), std::env::consts::OS.into()));
+        assert_eq!(entries.len(), 5);
+    }
+
+    #[test]
+    fn should_merge_the_flags_over_the_config() {
+        let config: Config = toml::from_str("sudo = true\ndefault = \"lock\"").unwrap();
+        let mut args = Args::parse_from([
+            "hpm",
+            "--backend",
+            "sysv",
+            "--retries",
+            "2",
+            "--dump-config",
+        ]);
+        config.apply(&mut args);
+
+        let effective_config = EffectiveConfig::new(&args);
+
+        assert_eq!(effective_config.backend, "sysv");
+        assert!(effective_config.sudo && !effective_config.pkexec);
+        assert_eq!(effective_config.default, "lock");
+        assert_eq!(effective_config.retries, 2);
+        assert_eq!(effective_config.color, "auto");
+        assert_eq!(effective_config.poll_interval, "1s");
+        assert_eq!(effective_config.poll_timeout, "1m");
+        assert!(toml::to_string(&effective_config).is_ok());
+    }
+
+    #[test]
+    fn should_share_the_poll_settings() {
+        let args = Args::parse_from(["hpm", "--poll-interval", "5s", "--poll-timeout", "2m"]);
+        let poll = Poll::new(&args);
+
+        assert_eq!(poll.interval, Duration::from_secs(5));
+        assert_eq!(poll.timeout, Duration::from_secs(120));
+        assert!(Args::try_parse_from(["hpm", "--poll-interval", "0s"]).is_err());
+    }
+
+    #[test]
+    fn should_confirm_by_typing_the_command_name() {
+        assert!(confirm_by_typing("kill", &mut &b"KILL\n"[..]).is_ok());
+        assert!(confirm_by_typing("restart", &mut &b" restart \n"[..]).is_ok());
+
+        let mismatch = confirm_by_typing("kill", &mut &b"y\n"[..]);
+        assert!(
+            matches!(mismatch, Err(Error::MismatchedConfirmation(expected)) if expected == "kill")
+        );
+
+        let eof = confirm_by_typing("kill", &mut &b""[..]);
+        assert!(matches!(eof, Err(Error::NoInput)));
+    }
+
+    #[test]
+    fn should_ask_for_a_yes_or_no() {
+        assert!(ask("Proceed?", &mut &b"YES\n"[..]).unwrap());
+        assert!(ask("Proceed?", &mut &b"y\n"[..]).unwrap());
+        assert!(!ask("Proceed?", &mut &b"\n"[..]).unwrap());
+        assert!(matches!(
+            ask("Proceed?", &mut &b""[..]),
+            Err(Error::NoInput)
+        ));
+
+        let aborted = Error::Aborted(Command::Kill.to_string());
+        assert_eq!(aborted.to_string(), "Kill is aborted by the user");
+        assert_eq!(exit_code(&aborted), 1);
+    }
+
+    #[test]
+    fn should_ask_for_an_optional_reason() {
+        assert_eq!(
+            ask_reason(&mut &b"kernel upgrade\n"[..]).unwrap(),
+            Some("kernel upgrade".to_string())
+        );
+        assert_eq!(ask_reason(&mut &b"\n"[..]).unwrap(), None);
+        assert!(matches!(
+            ask_reason(&mut &b"-h\n"[..]),
+            Err(Error::InvalidReason(_))
+        ));
+        assert!(matches!(ask_reason(&mut &b""[..]), Err(Error::NoInput)));
+
+        assert!(Args::try_parse_from(["hpm", "--ask-reason"]).is_err());
+        assert!(Args::try_parse_from(["hpm", "-i", "--ask-reason", "--no-wall"]).is_err());
+    }
+
+    #[test]
+    fn should_reject_confirm_typing_with_assume_yes() {
+        let parse_result = Args::try_parse_from(["hpm", "--confirm-typing", "-y", "kill"]);
+        assert!(parse_result.is_err());
+    }
+
+    #[test]
+    fn should_parse_the_subcommand_aliases() {
+        for (alias, canonical) in [
+            ("poweroff", "Kill"),
+            ("shutdown", "Kill"),
+            ("reboot", "Restart"),
+            ("logoff", "Logout"),
+        ] {
+            let args = Args::parse_from(["hpm", alias]);
+            assert_eq!(args.command.unwrap().to_string(), canonical);
+        }
+
+        let args = Args::parse_from(["hpm", "reboot", "--kexec"]);
+        assert!(matches!(
+            args.command,
+            Some(Command::Restart(RestartArgs { kexec: true, .. }))
+        ));
+    }
+
+    #[test]
+    fn should_parse_the_answer_by_index_or_name() {
+        let name_map = HashMap::from([("restart".to_string(), 1), ("hybridsleep".to_string(), 5)]);
+
+        assert_eq!(parse_answer("1\n", &name_map), Some(1));
+        assert_eq!(parse_answer("Restart\n", &name_map), Some(1));
+        assert_eq!(parse_answer("hybrid-sleep", &name_map), Some(5));
+        assert_eq!(parse_answer("reboot", &name_map), None);
+    }
+
+    #[test]
+    fn should_parse_the_default_choice() {
+        assert_eq!(parse_choice("logout"), Ok(DEFAULT_CHOICE));
+        assert_eq!(parse_choice("8"), Ok(8));
+        assert!(parse_choice("9").is_err());
+        assert!(parse_choice("status").is_err());
+
+        let config: Config = toml::from_str("default = \"lock\"").unwrap();
+        assert_eq!(config.default, Some(6));
+        assert!(toml::from_str::<Config>("default = \"reboot\"").is_err());
+    }
+
+    #[test]
+    fn should_fail_fast_without_input() {
+        let args = Args::parse_from(["hpm", "--backend", "echo", "-i"]);
+
+        let selection = prompt(choices(&args), DEFAULT_CHOICE, false, &mut &b""[..]);
+        assert!(matches!(selection, Err(Error::NoInput)));
+
+        let selection = prompt(choices(&args), DEFAULT_CHOICE, false, &mut &b"\n"[..]);
+        assert!(selection.is_ok_and(|choice| choice.cmd.to_string() == "Logout"));
+    }
+
+    #[test]
+    fn should_reject_verify_with_echo_backend() {
+        let args = Args::parse_from(["hpm", "-y", "--backend", "echo", "--verify", "restart"]);
+        let mut runner = |process: &mut Process| -> Result<Vec<u8>, crate::Error> {
+            panic!("{} should not run", process.command_line())
+        };
+
+        let err = try_run(args, Config::default(), &mut runner).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::UnverifiableCommand(_))
+        ));
+    }
+
+    #[test]
+    fn should_paint_only_when_enabled() {
+        let style = AnsiColor::Red.on_default();
+
+        assert_eq!(paint("Kill", style, false), "Kill");
+        assert_eq!(paint("Kill", style, true), "\u{1b}[31mKill\u{1b}[0m");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn should_not_wait_out_a_failed_restart() {
+        let mut process = Process::from_args("sh", ["-c", "exit 4"]);
+        let poll = Poll {
+            interval: Duration::from_millis(10),
+            timeout: Duration::from_secs(30),
+        };
+
+        let started = Instant::now();
+        let mut runner = MockRunner::default();
+        let err = verify_restart(&mut process, poll, true, &mut runner).unwrap_err();
+
+        assert!(matches!(
+            err.downcast_ref::<crate::Error>(),
+            Some(crate::Error::Exec { code: 4, .. })
+        ));
+        assert!(!runner.command_lines.is_empty());
+        assert!(
+            runner
+                .command_lines
+                .iter()
+                .all(|command_line| command_line == "systemctl is-system-running")
+        );
+        assert!(started.elapsed() < poll.timeout);
+    }
+
+    #[test]
+    fn should_parse_uptime() {
+        assert_eq!(parse_uptime("12345.67 45678.90\n"), Some(12345.67));
+        assert_eq!(parse_uptime(""), None);
+    }
+
+    #[test]
+    fn should_parse_the_timeout() {
+        let args = Args::parse_from(["hpm", "--timeout", "30s", "lock"]);
+        assert_eq!(args.timeout, Some(Duration::from_secs(30)));
+
+        assert!(Args::try_parse_from(["hpm", "--timeout", "soon", "lock"]).is_err());
+        assert!(
+            Args::try_parse_from(["hpm", "--timeout", "30s", "--retries", "2", "lock"]).is_ok()
+        );
+        assert!(Args::try_parse_from(["hpm", "--timeout", "30s", "--verify", "restart"]).is_err());
+    }
+
+    /// Records the command lines instead of running them.
+    #[derive(Default)]
+    struct MockRunner {
+        command_lines: Vec<String>,
+    }
+
+    impl Runner for MockRunner {
+        fn run(&mut self, process: &mut Process) -> Result<Vec<u8>, crate::Error> {
+            self.command_lines.push(process.command_line());
+            Ok(Vec::new())
+        }
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    #[test]
+    fn should_run_the_built_command() {
+        let args = Args::parse_from(["hpm", "-y", "-q", "--backend", "systemd", "kill"]);
+        let mut runner = MockRunner::default();
+
+        assert!(try_run(args, Config::default(), &mut runner).is_ok());
+        assert_eq!(
+            runner.command_lines,
+            ["systemd-inhibit --list --no-pager", "systemctl poweroff"]
+        );
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    #[test]
+    fn should_list_the_users_via_the_runner() {
+        let args = Args::parse_from(["hpm", "-q", "--backend", "systemd", "logout", "--user-all"]);
+        let mut command_lines = Vec::new();
+        let mut runner = |process: &mut Process| {
+            command_lines.push(process.command_line());
+            match process.command_line().as_str() {
+                "loginctl list-users --no-legend" => Ok(b"1000 alice\n1001 bob\n".to_vec()),
+                _ => Ok(Vec::new()),
+            }
+        };
+
+        assert!(try_run(args, Config::default(), &mut runner).is_ok());
+        assert_eq!(
+            command_lines,
+            [
+                "loginctl list-users --no-legend",
+                "loginctl terminate-user alice bob"
+            ]
+        );
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    #[test]
+    fn should_echo_the_user_listing_with_echo_backend() {
+        let args = Args::parse_from(["hpm", "-q", "--backend", "echo", "logout", "--user-all"]);
+        let mut runner = MockRunner::default();
+
+        assert!(try_run(args, Config::default(), &mut runner).is_ok());
+        assert_eq!(
+            runner.command_lines,
+            [
+                "echo loginctl list-users --no-legend",
+                "echo loginctl terminate-user"
+            ]
+        );
+    }
+
+    #[test]
+    fn should_notify_before_running_the_command() {
+        let args = Args::parse_from([
+            "hpm",
+            "-y",
+            "-q",
+            "--backend",
+            "echo",
+            "--notify",
+            "rebooting",
+            "--notify-delay",
+            "0s",
+            "lock",
+        ]);
+        let mut runner = MockRunner::default();
+
+        assert!(try_run(args, Config::default(), &mut runner).is_ok());
+        assert_eq!(
+            runner.command_lines,
+            ["echo wall rebooting", "echo loginctl lock-sessions"]
+        );
+    }
+
+    #[test]
+    fn should_abort_on_failed_notify_in_strict_mode() {
+        let args = Args::parse_from([
+            "hpm",
+            "-y",
+            "-q",
+            "--backend",
+            "echo",
+            "--strict",
+            "--notify",
+            "rebooting",
+            "lock",
+        ]);
+        let mut runner = |process: &mut Process| match process.command_line().as_str() {
+            "echo wall rebooting" => Err(crate::Error::BinaryDoesNotExist("wall".into())),
+            _ => panic!("{} should not run", process.command_line()),
+        };
+
+        let err = try_run(args, Config::default(), &mut runner).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::FailedToNotify(_))
+        ));
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    #[test]
+    fn should_abort_on_failed_inhibitor_listing_in_strict_mode() {
+        let mut command_lines = Vec::new();
+        let mut runner = |process: &mut Process| {
+            command_lines.push(process.command_line());
+            match process.program().to_str() {
+                Some("systemd-inhibit") => {
+                    Err(crate::Error::BinaryDoesNotExist("systemd-inhibit".into()))
+                }
+                _ => Ok(Vec::new()),
+            }
+        };
+
+        let args = Args::parse_from(["hpm", "-y", "-q", "--backend", "systemd", "kill"]);
+        assert!(try_run(args, Config::default(), &mut runner).is_ok());
+
+        let args = Args::parse_from([
+            "hpm",
+            "-y",
+            "-q",
+            "--backend",
+            "systemd",
+            "--strict",
+            "kill",
+        ]);
+        let err = try_run(args, Config::default(), &mut runner).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::FailedToListInhibitors(_))
+        ));
+
+        assert_eq!(
+            command_lines,
+            [
+                "systemd-inhibit --list --no-pager",
+                "systemctl poweroff",
+                "systemd-inhibit --list --no-pager"
+            ]
+        );
+    }
+
+    #[test]
+    fn should_suggest_sysv_when_systemd_is_not_init() {
+        let args = Args::parse_from(["hpm", "-q", "--backend", "systemd", "lock"]);
+        let mut runner = |process: &mut Process| -> Result<Vec<u8>, crate::Error> {
+            Err(crate::Error::Exec {
+                program: process.program().into(),
+                code: 1,
+                stdout: Vec::new(),
+                stderr: b"Failed to connect to bus: No such file or directory\n".to_vec(),
+            })
+        };
+
+        let err = try_run(args, Config::default(), &mut runner).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::SystemdIsNotInit(_))
+        ));
+        assert!(err.to_string().contains("--backend sysv"));
+    }
+
+    #[test]
+    fn should_not_suggest_sysv_for_other_programs() {
+        let args = Args::parse_from([
+            "hpm",
+            "-q",
+            "--backend",
+            "systemd",
+            "--unsafe-exec",
+            "exec",
+            "false",
+        ]);
+        let mut runner = |process: &mut Process| -> Result<Vec<u8>, crate::Error> {
+            Err(crate::Error::Exec {
+                program: process.wrapped_program().into(),
+                code: 1,
+                stdout: Vec::new(),
+                stderr: b"Failed to connect to bus: No such file or directory\n".to_vec(),
+            })
+        };
+
+        let err = try_run(args, Config::default(), &mut runner).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<crate::Error>(),
+            Some(crate::Error::Exec { program, .. }) if program == "false"
+        ));
+    }
+
+    #[test]
+    fn should_write_the_output_to_the_given_file() {
+        let path = std::env::temp_dir().join(format!("hpm-output-{}", std::process::id()));
+        let args = Args::parse_from([
+            "hpm",
+            "--backend",
+            "echo",
+            "--output-file",
+            path.to_str().unwrap(),
+            "lock",
+        ]);
+        let mut runner = |_: &mut Process| Ok(b"locked\n".to_vec());
+
+        let run_result = try_run(args, Config::default(), &mut runner);
+        let content = std::fs::read(&path);
+        let _ = std::fs::remove_file(&path);
+
+        assert!(run_result.is_ok());
+        assert_eq!(content.unwrap(), b"locked\n");
+
+        let missing_dir = std::env::temp_dir()
+            .join("hpm-missing-dir")
+            .join("output.log");
+        let err = write_file(&missing_dir, b"").unwrap_err();
+        assert!(
+            err.to_string()
+                .ends_with("(the directory of the file does not exist)")
+        );
+    }
+
+    #[test]
+    fn should_propagate_the_exit_code_of_the_command() {
+        let args = Args::parse_from([
+            "hpm",
+            "-q",
+            "--backend",
+            "echo",
+            "--propagate-exit-code",
+            "lock",
+        ]);
+        let mut runner = MockRunner::default();
+        assert_eq!(try_run(args, Config::default(), &mut runner).unwrap(), 0);
+        assert_eq!(runner.command_lines, ["echo loginctl lock-sessions"]);
+
+        let path = std::env::temp_dir().join(format!("hpm-propagate-{}", std::process::id()));
+        let args = Args::parse_from([
+            "hpm",
+            "--backend",
+            "echo",
+            "--propagate-exit-code",
+            "--timeout",
+            "30s",
+            "--output-file",
+            path.to_str().unwrap(),
+            "lock",
+        ]);
+        let mut runner = |process: &mut Process| -> Result<Vec<u8>, crate::Error> {
+            Err(crate::Error::Exec {
+                program: process.wrapped_program().into(),
+                code: 3,
+                stdout: b"warning\n".to_vec(),
+                stderr: b"already locked\n".to_vec(),
+            })
+        };
+        assert_eq!(try_run(args, Config::default(), &mut runner).unwrap(), 3);
+        let content = std::fs::read(&path);
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(content.unwrap(), b"warning\n");
+
+        let args = Args::parse_from([
+            "hpm",
+            "-q",
+            "--backend",
+            "echo",
+            "--pkexec",
+            "--propagate-exit-code",
+            "lock",
+        ]);
+        let mut runner = |process: &mut Process| -> Result<Vec<u8>, crate::Error> {
+            Err(crate::Error::Exec {
+                program: process.wrapped_program().into(),
+                code: PKEXEC_DISMISSED_ECODE,
+                stdout: Vec::new(),
+                stderr: Vec::new(),
+            })
+        };
+        let err = try_run(args, Config::default(), &mut runner).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::AuthorizationDismissed)
+        ));
+
+        assert!(Args::try_parse_from(["hpm", "--propagate-exit-code", "--retries", "2"]).is_err());
+    }
+
+    #[test]
+    fn should_exec_the_given_command_with_unsafe_exec() {
+        let args = Args::parse_from([
+            "hpm",
+            "--backend",
+            "echo",
+            "exec",
+            "--",
+            "systemctl",
+            "suspend-then-hibernate",
+        ]);
+        let cmd = args.command.as_ref().unwrap();
+        assert!(
+            matches!(build(cmd, &args), Err(Error::UnsafeExec(program)) if program == "systemctl")
+        );
+
+        let args = Args::parse_from([
+            "hpm",
+            "--backend",
+            "echo",
+            "--unsafe-exec",
+            "exec",
+            "systemctl",
+            "-q",
+            "suspend-then-hibernate",
+        ]);
+        let cmd = args.command.as_ref().unwrap();
+        assert_eq!(
+            build(cmd, &args).unwrap().command_line(),
+            "echo systemctl -q suspend-then-hibernate"
+        );
+
+        assert!(Args::try_parse_from(["hpm", "--unsafe-exec", "exec"]).is_err());
+    }
+
+    #[test]
+    fn should_pretend_success_without_running_anything() {
+        let args = Args::parse_from(["hpm", "-q", "--pretend-success", "kill"]);
+        let mut runner = |process: &mut Process| -> Result<Vec<u8>, crate::Error> {
+            panic!("{} should not run", process.command_line())
+        };
+
+        assert!(try_run(args, Config::default(), &mut runner).is_ok());
+    }
+
+    #[test]
+    fn should_name_the_error_kinds() {
+        let exec_err = crate::Error::Exec {
+            program: "systemctl".into(),
+            code: 4,
+            stdout: Vec::new(),
+            stderr: vec![],
+        };
+
+        assert_eq!(error_kind(&exec_err), "Exec");
+        assert_eq!(error_kind(&Error::NoInput), "NoInput");
+        assert_eq!(error_kind(&std::io::Error::other("io")), "Other");
+    }
+
+    #[test]
+    fn should_hint_at_permission_errors_of_the_binary() {
+        let denied = crate::Error::FailedToExecProcess(
+            "systemctl".into(),
+            std::io::Error::from(std::io::ErrorKind::PermissionDenied),
+        );
+        let missing = crate::Error::FailedToExecProcess(
+            "systemctl".into(),
+            std::io::Error::from(std::io::ErrorKind::NotFound),
+        );
+
+        assert_eq!(
+            error_hint(&denied),
+            Some("check that the binary and its interpreter are executable")
+        );
+        assert_eq!(error_hint(&missing), None);
+        assert_eq!(error_hint(&Error::NoInput), None);
+    }
+
+    #[test]
+    fn should_not_run_uptime_for_the_status_under_dry_run() {
+        let args = Args::parse_from(["hpm", "-n", "-q", "--backend", "echo", "status"]);
+        let mut runner = |process: &mut Process| -> Result<Vec<u8>, crate::Error> {
+            panic!("{} should not run", process.command_line())
+        };
+
+        assert_eq!(try_run(args, Config::default(), &mut runner).unwrap(), 0);
+    }
+
+    #[test]
+    fn should_run_uptime_for_the_status() {
+        let args = Args::parse_from(["hpm", "--backend", "echo", "--format", "json", "status"]);
+        let mut commands = Vec::new();
+        let mut runner = |process: &mut Process| {
+            commands.push(process.command_line());
+            Ok(b"up 1 hour\n".to_vec())
+        };
+
+        assert_eq!(try_run(args, Config::default(), &mut runner).unwrap(), 0);
+        assert_eq!(commands, ["echo uptime -p"]);
+
+        let args = Args::parse_from(["hpm", "--after", "10m", "status"]);
+        assert!(matches!(
+            build(&Command::Status, &args),
+            Err(Error::UnschedulableCommand(_))
+        ));
+        assert!(status(b"up 1 hour\n").ends_with("up 1 hour\n"));
+    }
+
+    #[test]
+    fn should_run_from_the_given_args() {
+        // The config of the host must not leak into the test, so it is looked up in an empty directory.
+        let config_home =
+            std::env::temp_dir().join(format!("hpm-config-home-{}", std::process::id()));
+        // SAFETY: no other test reads or writes XDG_CONFIG_HOME.
+        unsafe { std::env::set_var("XDG_CONFIG_HOME", &config_home) };
+
+        let exit_code = run_from(["hpm", "-n", "-q", "--backend", "echo", "lock"]);
+
+        assert_eq!(exit_code, ExitCode::SUCCESS);
+    }
+}
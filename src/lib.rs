@@ -1,2 +1,3 @@
+pub mod cli;
 pub mod process;
 pub use process::{Error, Process};
@@ -12,9 +12,19 @@
 //! [`std::io::stderr`]: std::io::stderr
 //! [`Error`]: crate::process::Error
 
+use log::{debug, info, warn};
 use std::{
+    collections::HashMap,
     ffi::{OsStr, OsString},
-    process::Command,
+    io::{Read, Write},
+    path::Path,
+    process::{Child, Command, ExitStatus, Output, Stdio},
+    sync::{
+        Mutex, MutexGuard, OnceLock, PoisonError,
+        atomic::{AtomicU32, Ordering},
+    },
+    thread,
+    time::{Duration, Instant},
 };
 
 /// The main Error type of [`crate::process`].
@@ -30,6 +40,11 @@ pub enum Error {
     /// [`crate::process::Process`]: crate::process::Process
     BinaryDoesNotExist(OsString),
 
+    /// Represents a [`std::process::Command`] without a program, e.g. `Command::new("")`.
+    ///
+    /// [`std::process::Command`]: std::process::Command
+    EmptyProgram,
+
     /// Represents a failed execution of the given [`std::process::Command`]
     /// to [`crate::process::Process`].
     /// Provides the program name and the originated [`std::io::Error`].
@@ -39,16 +54,64 @@ pub enum Error {
     FailedToExecProcess(OsString, std::io::Error),
 
     /// Represents a successful execution of a [`std::process::Command`] that resulted in an error.
-    /// Provides the exit code of the process, along with its [`std::io::stderr`] stream.
+    /// Provides the program name and the exit code of the process, along with its [`std::io::stdout`] and [`std::io::stderr`] streams,
+    /// e.g. for the commands that print a warning and exit with non-zero.
+    /// The program name is the wrapped one, so `systemctl` failing under `sudo` reports `systemctl`, see [`wrapped_program`].
+    ///
+    /// [`wrapped_program`]: crate::process::Process::wrapped_program
     ///
     /// [`crate::process::Process`]: crate::process::Process
+    /// [`std::io::stdout`]: std::io::stdout
     /// [`std::io::stderr`]: std::io::stderr
-    Exec(u8, Vec<u8>),
+    Exec {
+        program: OsString,
+        code: i32,
+        stdout: Vec<u8>,
+        stderr: Vec<u8>,
+    },
 
     /// Represents an interruption during the execution of a given [`std::process::Command`].
+    /// Provides the number of the signal that terminated the process, when it is known (Unix only).
+    ///
+    /// [`std::process::Command`]: std::process::Command
+    Interrupted(Option<i32>),
+
+    /// Represents an execution of a given [`std::process::Command`] that did not finish in time.
+    /// Provides the timeout that is exceeded.
     ///
     /// [`std::process::Command`]: std::process::Command
-    Interrupted,
+    TimedOut(Duration),
+}
+
+impl Error {
+    /// Returns the exit code that represents the error, following the shell conventions:
+    ///
+    /// - The exit code of the process for [`crate::process::Error::Exec`], or 255 if it does not fit.
+    /// - 128 + the signal number for [`crate::process::Error::Interrupted`], or 130 if the signal is unknown.
+    /// - 124 for [`crate::process::Error::TimedOut`], similar to `timeout`.
+    /// - 1 otherwise.
+    ///
+    /// ```
+    /// use hpm::Error;
+    ///
+    /// assert_eq!(Error::BinaryDoesNotExist("foo".into()).code(), 1);
+    /// assert_eq!(Error::Interrupted(Some(15)).code(), 143);
+    /// ```
+    ///
+    /// [`crate::process::Error::Exec`]: crate::process::Error::Exec
+    /// [`crate::process::Error::Interrupted`]: crate::process::Error::Interrupted
+    /// [`crate::process::Error::TimedOut`]: crate::process::Error::TimedOut
+    pub fn code(&self) -> u8 {
+        match self {
+            Error::BinaryDoesNotExist(_) => 1,
+            Error::EmptyProgram => 1,
+            Error::FailedToExecProcess(_, _) => 1,
+            Error::Exec { code, .. } => u8::try_from(*code).unwrap_or(u8::MAX),
+            Error::Interrupted(Some(signal)) => u8::try_from(128 + signal).unwrap_or(u8::MAX),
+            Error::Interrupted(None) => 130,
+            Error::TimedOut(_) => 124,
+        }
+    }
 }
 
 impl std::error::Error for Error {}
@@ -58,18 +121,43 @@ impl std::fmt::Display for Error {
             Error::BinaryDoesNotExist(binary) => {
                 write!(f, "the binary does not exist: {:?}", binary)
             }
+            Error::EmptyProgram => {
+                write!(f, "the program is empty")
+            }
             Error::FailedToExecProcess(binary, error) => {
-                write!(f, "failed to execute the binary {:?}: {}", binary, error)
+                write!(f, "failed to execute the binary {:?}: {}", binary, error)?;
+
+                match error.kind() {
+                    std::io::ErrorKind::NotFound => {
+                        write!(f, " (the binary or its interpreter is missing)")
+                    }
+                    _ => Ok(()),
+                }
             }
-            Error::Exec(_, vec) => {
-                let process_err = String::from_utf8(vec.to_owned())
-                    .expect("the process output should be valid UTF-8");
+            Error::Exec {
+                program,
+                code,
+                stderr,
+                ..
+            } => {
+                write!(f, "{}: ", program.to_string_lossy())?;
+
+                if let Some(explanation) = explain_exit_code(program, *code) {
+                    write!(f, "{}: {}: ", code, explanation)?;
+                }
 
-                write!(f, "{process_err}")
+                write!(f, "{}", String::from_utf8_lossy(stderr))
             }
-            Error::Interrupted => {
+            Error::Interrupted(Some(signal)) => match signal_name(*signal) {
+                Some(name) => write!(f, "interrupted by signal {} ({})", signal, name),
+                None => write!(f, "interrupted by signal {}", signal),
+            },
+            Error::Interrupted(None) => {
                 write!(f, "interrupted by the host")
             }
+            Error::TimedOut(timeout) => {
+                write!(f, "timed out after {:?}", timeout)
+            }
         }
     }
 }
@@ -86,23 +174,446 @@ impl std::fmt::Display for Error {
 /// [`crate::process::Process::new`]: crate::process::Process::new
 /// [`crate::process::Process::exec`]: crate::process::Process::exec
 /// [`std::process::Command`]: std::process::Command
-pub struct Process(Command);
+pub struct Process {
+    cmd: Command,
+    stdin: Option<Vec<u8>>,
+    kill_on_drop: bool,
+    inherit_stdio: bool,
+    timeout: Option<Duration>,
+    wrappers: usize,
+    children: Vec<Child>,
+}
 
 impl Process {
     /// Creates a new Process.
     pub fn new(cmd: Command) -> Self {
-        Self(cmd)
+        Self {
+            cmd,
+            stdin: None,
+            kill_on_drop: false,
+            inherit_stdio: false,
+            timeout: None,
+            wrappers: 0,
+            children: Vec::new(),
+        }
+    }
+
+    /// Creates a new Process from the given program and its arguments,
+    /// without building the [`std::process::Command`] beforehand.
+    ///
+    /// ```
+    /// use hpm::Process;
+    ///
+    /// let process = Process::from_args("systemctl", ["poweroff"]);
+    /// assert_eq!(process.command_line(), "systemctl poweroff");
+    /// ```
+    ///
+    /// [`std::process::Command`]: std::process::Command
+    pub fn from_args<I, S>(program: impl AsRef<OsStr>, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        let mut cmd = Command::new(program);
+        cmd.args(args);
+
+        Self::new(cmd)
+    }
+
+    /// Returns the program of the wrapped [`std::process::Command`].
+    ///
+    /// ```
+    /// use hpm::Process;
+    /// use std::process::Command;
+    ///
+    /// let process = Process::new(Command::new("systemctl"));
+    /// assert_eq!(process.program(), "systemctl");
+    /// ```
+    ///
+    /// [`std::process::Command`]: std::process::Command
+    pub fn program(&self) -> &OsStr {
+        self.cmd.get_program()
+    }
+
+    /// Returns the program that is wrapped by [`with_sudo`] or [`with_pkexec`], i.e. the one that does the actual work,
+    /// or the same as [`program`] when it is not wrapped.
+    ///
+    /// ```
+    /// use hpm::Process;
+    ///
+    /// let process = Process::from_args("systemctl", ["reboot"]).with_sudo();
+    /// assert_eq!(process.program(), "sudo");
+    /// assert_eq!(process.wrapped_program(), "systemctl");
+    /// ```
+    ///
+    /// [`with_sudo`]: crate::process::Process::with_sudo
+    /// [`with_pkexec`]: crate::process::Process::with_pkexec
+    /// [`program`]: crate::process::Process::program
+    pub fn wrapped_program(&self) -> &OsStr {
+        match self.wrappers {
+            0 => self.program(),
+            wrappers => self.get_args().nth(wrappers - 1).unwrap_or(self.program()),
+        }
+    }
+
+    /// Returns the arguments of the wrapped [`std::process::Command`], without the program.
+    ///
+    /// ```
+    /// use hpm::Process;
+    /// use std::process::Command;
+    ///
+    /// let mut cmd = Command::new("systemctl");
+    /// cmd.arg("reboot");
+    ///
+    /// let process = Process::new(cmd);
+    /// assert!(process.get_args().eq(["reboot"]));
+    /// ```
+    ///
+    /// [`std::process::Command`]: std::process::Command
+    pub fn get_args(&self) -> impl Iterator<Item = &OsStr> {
+        self.cmd.get_args()
+    }
+
+    /// Reconstructs the command line of the wrapped [`std::process::Command`],
+    /// e.g. `systemctl reboot`.
+    ///
+    /// Non UTF-8 parts are converted lossily, so the result is meant to be displayed, not executed.
+    ///
+    /// [`std::process::Command`]: std::process::Command
+    pub fn command_line(&self) -> String {
+        std::iter::once(self.program())
+            .chain(self.get_args())
+            .map(|part| part.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Reconstructs the command line of the wrapped [`std::process::Command`] the way it would be executed,
+    /// with the parts quoted for a POSIX shell, so that the result can be copy-pasted to run it.
+    ///
+    /// ```
+    /// use hpm::Process;
+    ///
+    /// let process = Process::from_args("shutdown", ["-r", "+5", "kernel upgrade"]);
+    /// assert_eq!(process.dry_run(), "shutdown -r +5 'kernel upgrade'");
+    /// ```
+    ///
+    /// [`std::process::Command`]: std::process::Command
+    pub fn dry_run(&self) -> String {
+        std::iter::once(self.program())
+            .chain(self.get_args())
+            .map(|part| shell_quote(&part.to_string_lossy()))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Rewraps the [`std::process::Command`] to be executed via `sudo`,
+    /// e.g. `systemctl poweroff` becomes `sudo systemctl poweroff`.
+    ///
+    /// Since `sudo` becomes the program, [`exec`] validates `sudo` instead of the original program.
+    ///
+    /// [`exec`]: crate::process::Process::exec
+    /// [`std::process::Command`]: std::process::Command
+    pub fn with_sudo(self) -> Process {
+        self.wrap("sudo")
+    }
+
+    /// Rewraps the [`std::process::Command`] to be executed via `pkexec` (Polkit),
+    /// e.g. `systemctl poweroff` becomes `pkexec systemctl poweroff`.
+    ///
+    /// Since `pkexec` becomes the program, [`exec`] validates `pkexec` instead of the original program.
+    ///
+    /// [`exec`]: crate::process::Process::exec
+    /// [`std::process::Command`]: std::process::Command
+    pub fn with_pkexec(self) -> Process {
+        self.wrap("pkexec")
+    }
+
+    fn wrap(mut self, program: impl AsRef<OsStr>) -> Process {
+        let mut cmd = Command::new(program);
+        cmd.arg(self.program()).args(self.get_args());
+
+        for (key, val) in self.cmd.get_envs() {
+            match val {
+                Some(val) => cmd.env(key, val),
+                None => cmd.env_remove(key),
+            };
+        }
+
+        if let Some(dir) = self.cmd.get_current_dir() {
+            cmd.current_dir(dir);
+        }
+
+        Process {
+            cmd,
+            stdin: self.stdin.take(),
+            kill_on_drop: self.kill_on_drop,
+            inherit_stdio: self.inherit_stdio,
+            timeout: self.timeout,
+            wrappers: self.wrappers + 1,
+            children: std::mem::take(&mut self.children),
+        }
+    }
+
+    /// Adds an argument to the wrapped [`std::process::Command`].
+    ///
+    /// [`std::process::Command`]: std::process::Command
+    pub fn arg(&mut self, arg: impl AsRef<OsStr>) -> &mut Self {
+        self.cmd.arg(arg);
+        self
+    }
+
+    /// Adds multiple arguments to the wrapped [`std::process::Command`].
+    ///
+    /// [`std::process::Command`]: std::process::Command
+    pub fn args<I, S>(&mut self, args: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        self.cmd.args(args);
+        self
+    }
+
+    /// Sets the working directory of the wrapped [`std::process::Command`].
+    ///
+    /// [`std::process::Command`]: std::process::Command
+    pub fn current_dir(&mut self, dir: impl AsRef<Path>) -> &mut Self {
+        self.cmd.current_dir(dir);
+        self
+    }
+
+    /// Sets an environment variable for the wrapped [`std::process::Command`].
+    ///
+    /// [`std::process::Command`]: std::process::Command
+    pub fn env(&mut self, key: impl AsRef<OsStr>, val: impl AsRef<OsStr>) -> &mut Self {
+        self.cmd.env(key, val);
+        self
+    }
+
+    /// Clears the environment variables of the wrapped [`std::process::Command`],
+    /// including the ones that are inherited from the caller.
+    ///
+    /// [`std::process::Command`]: std::process::Command
+    pub fn env_clear(&mut self) -> &mut Self {
+        self.cmd.env_clear();
+        self
+    }
+
+    /// Schedules the given closure to run in the forked child right before the program is executed,
+    /// e.g. to drop the privileges of a daemon via `setgid`/`setuid` or to start a new session via `setsid`.
+    /// It delegates to [`std::os::unix::process::CommandExt::pre_exec`].
+    ///
+    /// The closure is registered on the current [`std::process::Command`], so call [`with_sudo`] or [`with_pkexec`] first
+    /// since they rebuild it. It does not apply to [`exec_async`] either.
+    ///
+    /// ```
+    /// use hpm::Process;
+    ///
+    /// let mut process = Process::from_args("true", [] as [&str; 0]);
+    ///
+    /// // SAFETY: the closure does not allocate, lock or touch the state of the parent.
+    /// unsafe { process.pre_exec(|| Ok(())) };
+    ///
+    /// assert!(process.exec().is_ok());
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// The closure runs in the child after `fork`, where only the parent's calling thread exists.
+    /// Therefore it must only call async-signal-safe functions: it must not allocate, take a lock
+    /// (including the ones of stdio or the logger) or rely on the state of the other threads, which may deadlock the child.
+    /// It must not close or reuse the file descriptors of the parent either, since the pipes of the streams are set up beforehand.
+    /// An [`std::io::Error`] returned by the closure aborts the execution with [`crate::process::Error::FailedToExecProcess`].
+    ///
+    /// [`std::os::unix::process::CommandExt::pre_exec`]: std::os::unix::process::CommandExt::pre_exec
+    /// [`std::process::Command`]: std::process::Command
+    /// [`std::io::Error`]: std::io::Error
+    /// [`with_sudo`]: crate::process::Process::with_sudo
+    /// [`with_pkexec`]: crate::process::Process::with_pkexec
+    /// [`exec_async`]: crate::process::Process::exec_async
+    /// [`crate::process::Error::FailedToExecProcess`]: crate::process::Error::FailedToExecProcess
+    #[cfg(unix)]
+    pub unsafe fn pre_exec<F>(&mut self, f: F) -> &mut Self
+    where
+        F: FnMut() -> std::io::Result<()> + Send + Sync + 'static,
+    {
+        use std::os::unix::process::CommandExt;
+
+        // SAFETY: the requirements are forwarded to the caller, see the Safety section.
+        unsafe { self.cmd.pre_exec(f) };
+        self
+    }
+
+    /// Feeds the given bytes to the stdin of the command during [`output`] and the methods built on it, e.g. [`exec`],
+    /// e.g. for the commands that read a confirmation on stdin.
+    /// See [`timeout`] for the methods that ignore it.
+    ///
+    /// [`exec`]: crate::process::Process::exec
+    /// [`output`]: crate::process::Process::output
+    /// [`timeout`]: crate::process::Process::timeout
+    pub fn stdin_bytes(&mut self, data: Vec<u8>) -> &mut Self {
+        self.stdin = Some(data);
+        self
+    }
+
+    /// Lets the command use the terminal of the caller during [`output`] and the methods built on it, e.g. [`exec`],
+    /// i.e. the streams are inherited instead of captured, e.g. for the password prompt of `sudo`.
+    /// See [`timeout`] for the methods that ignore it.
+    ///
+    /// Since nothing is captured, [`exec`] returns an empty [`std::io::stdout`] stream on success
+    /// and [`crate::process::Error::Exec`] holds an empty stderr on failure. The bytes of [`stdin_bytes`] are still fed to the command.
+    ///
+    /// [`exec`]: crate::process::Process::exec
+    /// [`output`]: crate::process::Process::output
+    /// [`timeout`]: crate::process::Process::timeout
+    /// [`stdin_bytes`]: crate::process::Process::stdin_bytes
+    /// [`std::io::stdout`]: std::io::stdout
+    /// [`crate::process::Error::Exec`]: crate::process::Error::Exec
+    pub fn inherit_stdio(&mut self, yes: bool) -> &mut Self {
+        self.inherit_stdio = yes;
+        self
+    }
+
+    /// Bounds the executions of the command by the given timeout.
+    /// When the timeout is exceeded, the command is killed and [`crate::process::Error::TimedOut`] is returned.
+    ///
+    /// Without a timeout (the default), the command is waited indefinitely.
+    ///
+    /// The timeout, along with [`stdin_bytes`] and [`inherit_stdio`], is honored by [`output`] and the methods built on it:
+    /// [`exec`], [`exec_unchecked`], [`exec_with_code`], [`exec_with_stderr`], [`exec_timed`], [`exec_keep_stdout`]
+    /// and [`exec_with_retries`] (per attempt). [`exec_streaming`], [`exec_combined`], [`exec_async`] and [`spawn_detached`]
+    /// set up their own streams and ignore them.
+    ///
+    /// ```
+    /// use hpm::{Error, Process};
+    /// use std::time::Duration;
+    ///
+    /// let mut process = Process::from_args("sleep", ["5"]);
+    /// process.timeout(Duration::from_millis(100));
+    ///
+    /// assert!(matches!(process.exec(), Err(Error::TimedOut(_))));
+    /// ```
+    ///
+    /// [`exec`]: crate::process::Process::exec
+    /// [`output`]: crate::process::Process::output
+    /// [`stdin_bytes`]: crate::process::Process::stdin_bytes
+    /// [`inherit_stdio`]: crate::process::Process::inherit_stdio
+    /// [`exec_unchecked`]: crate::process::Process::exec_unchecked
+    /// [`exec_with_code`]: crate::process::Process::exec_with_code
+    /// [`exec_with_stderr`]: crate::process::Process::exec_with_stderr
+    /// [`exec_timed`]: crate::process::Process::exec_timed
+    /// [`exec_keep_stdout`]: crate::process::Process::exec_keep_stdout
+    /// [`exec_with_retries`]: crate::process::Process::exec_with_retries
+    /// [`exec_streaming`]: crate::process::Process::exec_streaming
+    /// [`exec_combined`]: crate::process::Process::exec_combined
+    /// [`exec_async`]: crate::process::Process::exec_async
+    /// [`spawn_detached`]: crate::process::Process::spawn_detached
+    /// [`crate::process::Error::TimedOut`]: crate::process::Error::TimedOut
+    pub fn timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.timeout = Some(timeout);
+        self
     }
 
-    fn get_process_name(&self) -> &OsStr {
-        self.0.get_program()
+    /// Kills the commands that are still running when the [`crate::process::Process`] is dropped,
+    /// i.e. the ones started by [`spawn_detached`], or by [`exec_async`] when its future is dropped.
+    ///
+    /// It is disabled by default, so that a detached command outlives the [`crate::process::Process`].
+    ///
+    /// [`crate::process::Process`]: crate::process::Process
+    /// [`spawn_detached`]: crate::process::Process::spawn_detached
+    /// [`exec_async`]: crate::process::Process::exec_async
+    pub fn kill_on_drop(&mut self, kill_on_drop: bool) -> &mut Self {
+        self.kill_on_drop = kill_on_drop;
+        self
+    }
+
+    /// Checks whether the program of the wrapped [`std::process::Command`] is accessible on the host,
+    /// without executing it. Every execution method runs the same check beforehand.
+    ///
+    /// The result of the `$PATH` lookup is memoized for each program, so that the next [`crate::process::Process`]
+    /// of the same program skips the lookup until [`clear_lookup_cache`] is called. A path, absolute or relative, is checked every time.
+    ///
+    /// ```
+    /// use hpm::{Error, Process};
+    /// use std::process::Command;
+    ///
+    /// assert!(Process::new(Command::new("echo")).validate().is_ok());
+    /// assert!(matches!(
+    ///     Process::new(Command::new("this-binary-does-not-exist")).validate(),
+    ///     Err(Error::BinaryDoesNotExist(_))
+    /// ));
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// [`crate::process::Error::EmptyProgram`] - Originates when the program is empty.
+    /// [`crate::process::Error::BinaryDoesNotExist`] - Originates when the program cannot be found.
+    ///
+    /// [`crate::process::Error::EmptyProgram`]: crate::process::Error::EmptyProgram
+    /// [`crate::process::Error::BinaryDoesNotExist`]: crate::process::Error::BinaryDoesNotExist
+    /// [`crate::process::Process`]: crate::process::Process
+    /// [`clear_lookup_cache`]: crate::process::clear_lookup_cache
+    /// [`std::process::Command`]: std::process::Command
+    pub fn validate(&self) -> Result<(), Error> {
+        let process_name = self.program();
+        if process_name.is_empty() {
+            return Err(Error::EmptyProgram);
+        }
+
+        // An absolute path does not need $PATH, which may be stripped, e.g. in a sandbox.
+        let path = Path::new(process_name);
+        if path.is_absolute() {
+            return match is_executable(path) {
+                true => Ok(()),
+                false => {
+                    debug!("{} is not an executable", path.display());
+                    Err(Error::BinaryDoesNotExist(process_name.to_os_string()))
+                }
+            };
+        }
+
+        // A relative path, e.g. ./bin/program, depends on the working directory rather than
+        // on $PATH, so only a bare program name is memoized.
+        let cacheable = path.components().count() == 1;
+        let cached = cacheable
+            .then(|| lookup_cache().get(process_name).copied())
+            .flatten();
+        let resolved = match cached {
+            Some(resolved) => {
+                debug!("using the cached lookup of {:?}", process_name);
+                resolved
+            }
+            None => {
+                let resolved = match which::which(process_name) {
+                    Ok(path) => {
+                        debug!("resolved {:?} to {}", process_name, path.display());
+                        true
+                    }
+                    Err(err) => {
+                        debug!("failed to resolve {:?}: {}", process_name, err);
+                        false
+                    }
+                };
+                if cacheable {
+                    lookup_cache().insert(process_name.to_os_string(), resolved);
+                }
+                resolved
+            }
+        };
+
+        match resolved {
+            true => Ok(()),
+            false => Err(Error::BinaryDoesNotExist(process_name.to_os_string())),
+        }
     }
 
-    fn validate(&self) -> Result<(), Error> {
-        let process_name = self.get_process_name();
-        which::which(process_name)
-            .map(|_| ())
-            .map_err(|_| Error::BinaryDoesNotExist(process_name.to_os_string()))
+    fn log_exec(&self) {
+        info!("executing {}", self);
+
+        if let Some(dir) = self.cmd.get_current_dir() {
+            debug!("working directory: {}", dir.display());
+        }
     }
 
     /// [`exec`] is the only meaningful interaction point of a [`crate::process::Process`].
@@ -115,7 +626,7 @@ impl Process {
     /// # Errors
     ///
     /// [`crate::process::Error::FailedToExecProcess`] - Originates when the execution of Command fails.
-    /// [`crate::process::Error::Interrupted`] - Originates when the execution of the command is interrupted.
+    /// [`crate::process::Error::Interrupted`] - Originates when the execution of the command is interrupted, e.g. by a signal.
     /// [`crate::process::Error::Exec`] - Originates when the Command is executed successfully, but the received exit code is greater than zero.
     /// It holds the exit code along with the [`std::io::stderr`] stream.
     ///
@@ -128,76 +639,1503 @@ impl Process {
     /// [`std::io::stderr`]: std::io::stderr
     /// [`std::process::Process`]: std::process::Process
     pub fn exec(&mut self) -> Result<Vec<u8>, Error> {
+        let proc_output = self.output()?;
+        into_result(self.wrapped_program(), proc_output)
+    }
+
+    /// [`output`] is the low-level counterpart of [`exec`].
+    /// It validates the program, executes the command and returns its raw [`std::process::Output`],
+    /// without interpreting the exit status. It is up to the caller to decide what a failure is.
+    ///
+    /// # Errors
+    ///
+    /// [`crate::process::Error::BinaryDoesNotExist`] - Originates when the program cannot be found.
+    /// [`crate::process::Error::FailedToExecProcess`] - Originates when the execution of Command fails.
+    ///
+    /// [`exec`]: crate::process::Process::exec
+    /// [`output`]: crate::process::Process::output
+    /// [`crate::process::Error::BinaryDoesNotExist`]: crate::process::Error::BinaryDoesNotExist
+    /// [`crate::process::Error::FailedToExecProcess`]: crate::process::Error::FailedToExecProcess
+    /// [`std::process::Output`]: std::process::Output
+    pub fn output(&mut self) -> Result<Output, Error> {
         self.validate()?;
+        self.output_unchecked()
+    }
 
-        let proc_output = self
-            .0
-            .output()
-            .map_err(|err| Error::FailedToExecProcess(self.get_process_name().into(), err))?;
+    fn output_unchecked(&mut self) -> Result<Output, Error> {
+        self.log_exec();
+
+        // Similar to Command::output, the stdin is not inherited unless there is something to feed.
+        let stdin = match self.stdin {
+            Some(_) => Stdio::piped(),
+            None if self.inherit_stdio => Stdio::inherit(),
+            None => Stdio::null(),
+        };
+        let (stdout, stderr) = match self.inherit_stdio {
+            true => (Stdio::inherit(), Stdio::inherit()),
+            false => (Stdio::piped(), Stdio::piped()),
+        };
 
-        let ecode = proc_output.status.code().ok_or(Error::Interrupted)? as u8;
+        let mut child = self
+            .cmd
+            .stdin(stdin)
+            .stdout(stdout)
+            .stderr(stderr)
+            .spawn()
+            .map_err(|err| Error::FailedToExecProcess(self.program().into(), err))?;
+        let _forwarding = Forwarding::to(&child);
 
-        if proc_output.status.success() {
-            return Ok(proc_output.stdout);
+        // Written on a separate thread, so that a full stdout pipe cannot block the writer and the reader.
+        // The child may exit without reading its stdin, hence the ignored write error.
+        let writer = self.stdin.clone().and_then(|data| {
+            child
+                .stdin
+                .take()
+                .map(|mut stdin| thread::spawn(move || stdin.write_all(&data)))
+        });
+
+        let proc_output = match self.timeout {
+            Some(timeout) => self.wait_with_deadline(child, timeout)?,
+            None => child
+                .wait_with_output()
+                .map_err(|err| Error::FailedToExecProcess(self.program().into(), err))?,
+        };
+
+        if let Some(writer) = writer {
+            let _ = writer.join();
         }
 
-        Err(Error::Exec(ecode, proc_output.stderr))
+        Ok(proc_output)
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use std::io::Read;
+    fn wait_with_deadline(&self, mut child: Child, timeout: Duration) -> Result<Output, Error> {
+        // The streams are drained on their own threads so a chatty child cannot block on a full pipe.
+        let stdout_reader = drain(child.stdout.take());
+        let stderr_reader = drain(child.stderr.take());
 
-    use super::*;
+        let deadline = Instant::now() + timeout;
+        let status = loop {
+            let wait_result = child
+                .try_wait()
+                .map_err(|err| Error::FailedToExecProcess(self.program().into(), err))?;
 
-    #[test]
-    fn should_return_proper_program_name() {
-        let cmd = Command::new("echo");
-        assert_eq!(Process::new(cmd).get_process_name(), "echo")
+            if let Some(status) = wait_result {
+                break status;
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                // The child might have exited right before the kill, hence the ignored error.
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(Error::TimedOut(timeout));
+            }
+
+            thread::sleep(POLL_INTERVAL.min(deadline - now));
+        };
+
+        Ok(Output {
+            status,
+            stdout: stdout_reader.join().unwrap_or_default(),
+            stderr: stderr_reader.join().unwrap_or_default(),
+        })
     }
 
-    #[test]
-    fn should_not_exec_nonexistent_binaries() {
-        let cmd = Command::new("this-binary-does-not-exist");
-        let mut process = Process::new(cmd);
+    /// [`exec_unchecked`] is the same with [`exec`], except that it skips [`validate`] and spawns the command directly,
+    /// e.g. when the `$PATH` lookup is wrong about the program.
+    ///
+    /// A missing program is then reported by the spawn itself.
+    ///
+    /// # Errors
+    ///
+    /// The errors are the same with [`exec`], except that a missing program is reported as
+    /// [`crate::process::Error::FailedToExecProcess`] instead of [`crate::process::Error::BinaryDoesNotExist`].
+    ///
+    /// [`exec`]: crate::process::Process::exec
+    /// [`exec_unchecked`]: crate::process::Process::exec_unchecked
+    /// [`validate`]: crate::process::Process::validate
+    /// [`crate::process::Error::FailedToExecProcess`]: crate::process::Error::FailedToExecProcess
+    /// [`crate::process::Error::BinaryDoesNotExist`]: crate::process::Error::BinaryDoesNotExist
+    pub fn exec_unchecked(&mut self) -> Result<Vec<u8>, Error> {
+        let proc_output = self.output_unchecked()?;
+        into_result(self.wrapped_program(), proc_output)
+    }
 
-        let validate_result = process.validate();
-        assert!(validate_result.is_err());
+    /// [`exec_with_code`] is the same with [`exec`], except that it returns the exit code
+    /// along with the [`std::io::stdout`] stream of the command.
+    ///
+    /// A non-zero exit code is returned as is instead of [`crate::process::Error::Exec`],
+    /// e.g. for the callers that report the exit code regardless of the result.
+    ///
+    /// # Errors
+    ///
+    /// [`crate::process::Error::BinaryDoesNotExist`] - Originates when the program cannot be found.
+    /// [`crate::process::Error::FailedToExecProcess`] - Originates when the execution of Command fails.
+    /// [`crate::process::Error::Interrupted`] - Originates when the execution of the command is interrupted, e.g. by a signal.
+    ///
+    /// [`exec`]: crate::process::Process::exec
+    /// [`exec_with_code`]: crate::process::Process::exec_with_code
+    /// [`crate::process::Error::Exec`]: crate::process::Error::Exec
+    /// [`crate::process::Error::BinaryDoesNotExist`]: crate::process::Error::BinaryDoesNotExist
+    /// [`crate::process::Error::FailedToExecProcess`]: crate::process::Error::FailedToExecProcess
+    /// [`crate::process::Error::Interrupted`]: crate::process::Error::Interrupted
+    /// [`std::io::stdout`]: std::io::stdout
+    pub fn exec_with_code(&mut self) -> Result<(Vec<u8>, i32), Error> {
+        let proc_output = self.output()?;
+        debug!("the process exited with {}", proc_output.status);
 
-        let exec_result = process.exec();
-        assert!(exec_result.is_err());
+        let ecode = proc_output
+            .status
+            .code()
+            .ok_or_else(|| Error::Interrupted(signal(&proc_output.status)))?;
 
-        let validate_err_str = format!("{}", validate_result.unwrap_err());
-        let exec_err_str = format!("{}", exec_result.unwrap_err());
+        Ok((proc_output.stdout, ecode))
+    }
 
-        assert_eq!(validate_err_str, exec_err_str);
+    /// [`exec_with_stderr`] is the same with [`exec`], except that it returns the [`std::io::stderr`] stream
+    /// along with the [`std::io::stdout`] stream of the command, e.g. for the warnings of a successful command.
+    ///
+    /// # Errors
+    ///
+    /// The errors are the same with [`exec`].
+    ///
+    /// [`exec`]: crate::process::Process::exec
+    /// [`exec_with_stderr`]: crate::process::Process::exec_with_stderr
+    /// [`std::io::stdout`]: std::io::stdout
+    /// [`std::io::stderr`]: std::io::stderr
+    pub fn exec_with_stderr(&mut self) -> Result<(Vec<u8>, Vec<u8>), Error> {
+        let mut proc_output = self.output()?;
+        let stderr = std::mem::take(&mut proc_output.stderr);
+
+        match into_result(self.wrapped_program(), proc_output) {
+            Ok(stdout) => Ok((stdout, stderr)),
+            Err(Error::Exec {
+                program,
+                code,
+                stdout,
+                ..
+            }) => Err(Error::Exec {
+                program,
+                code,
+                stdout,
+                stderr,
+            }),
+            Err(err) => Err(err),
+        }
     }
 
-    #[test]
-    fn should_propagate_stderr_of_child_process() {
-        let mut cmd = Command::new("ls");
-        cmd.arg("this-file-does-not-exist");
+    /// [`exec_timed`] is the same with [`exec`], except that it returns how long the command ran
+    /// along with the [`std::io::stdout`] stream of the command, e.g. to monitor how long the teardown of a reboot takes.
+    ///
+    /// The duration is the wall-clock time between the spawn and the exit of the command, the `$PATH` lookup is not included.
+    ///
+    /// # Errors
+    ///
+    /// The errors are the same with [`exec`].
+    ///
+    /// [`exec`]: crate::process::Process::exec
+    /// [`exec_timed`]: crate::process::Process::exec_timed
+    /// [`std::io::stdout`]: std::io::stdout
+    pub fn exec_timed(&mut self) -> Result<(Vec<u8>, Duration), Error> {
+        self.validate()?;
 
-        let mut process = Process::new(cmd);
-        let exec_result = process.exec();
+        let started = Instant::now();
+        let proc_output = self.output_unchecked()?;
+        let elapsed = started.elapsed();
+        debug!("the process ran for {:?}", elapsed);
 
-        assert!(exec_result.is_err_and(|err| {
-            if let Error::Exec(ecode, stderr) = err {
-                ecode > 0u8 && stderr.bytes().count() > 0
-            } else {
-                false
-            }
-        }));
+        into_result(self.wrapped_program(), proc_output).map(|stdout| (stdout, elapsed))
     }
 
-    #[test]
-    fn should_propagate_stdout_of_child_process() {
-        let cmd = Command::new("ls");
+    /// [`exec_keep_stdout`] is the lenient counterpart of [`exec`].
+    /// It returns the [`std::io::stdout`] stream along with the exit code, regardless of the exit code,
+    /// e.g. for the commands that print warnings and exit with non-zero.
+    ///
+    /// It is deprecated in favor of [`exec_with_code`], which returns the same.
+    ///
+    /// # Errors
+    ///
+    /// The errors are the same with [`exec_with_code`].
+    ///
+    /// [`exec`]: crate::process::Process::exec
+    /// [`exec_keep_stdout`]: crate::process::Process::exec_keep_stdout
+    /// [`exec_with_code`]: crate::process::Process::exec_with_code
+    /// [`std::io::stdout`]: std::io::stdout
+    #[deprecated(note = "use `Process::exec_with_code` instead")]
+    pub fn exec_keep_stdout(&mut self) -> Result<(Vec<u8>, i32), Error> {
+        self.exec_with_code()
+    }
+
+    /// [`exec_streaming`] is the live counterpart of [`exec`].
+    /// It validates the program of the user provided [`std::process::Command`],
+    /// executes the command with inherited output streams and waits it.
+    ///
+    /// Since the output streams are written directly to [`std::io::stdout`] and [`std::io::stderr`] of the caller,
+    /// nothing is buffered. This makes it suitable for long-running commands.
+    ///
+    /// # Errors
+    ///
+    /// The errors are the same with [`exec`], except that [`crate::process::Error::Exec`] holds an empty stderr
+    /// since it is already streamed to the caller.
+    ///
+    /// [`exec`]: crate::process::Process::exec
+    /// [`exec_streaming`]: crate::process::Process::exec_streaming
+    /// [`crate::process::Error::Exec`]: crate::process::Error::Exec
+    /// [`std::io::stdout`]: std::io::stdout
+    /// [`std::io::stderr`]: std::io::stderr
+    /// [`std::process::Command`]: std::process::Command
+    pub fn exec_streaming(&mut self) -> Result<(), Error> {
+        self.validate()?;
+        self.log_exec();
+
+        let mut child = self
+            .cmd
+            .stdin(Stdio::inherit())
+            .stdout(Stdio::inherit())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .map_err(|err| Error::FailedToExecProcess(self.program().into(), err))?;
+        let _forwarding = Forwarding::to(&child);
+
+        let status = child
+            .wait()
+            .map_err(|err| Error::FailedToExecProcess(self.program().into(), err))?;
+
+        into_result(
+            self.wrapped_program(),
+            Output {
+                status,
+                stdout: Vec::new(),
+                stderr: Vec::new(),
+            },
+        )
+        .map(|_| ())
+    }
+
+    /// [`exec_combined`] validates the program of the user provided [`std::process::Command`],
+    /// executes the command with its [`std::io::stderr`] redirected to its [`std::io::stdout`] and waits it.
+    ///
+    /// Unlike [`exec`], it returns the combined stream along with the exit code, regardless of the exit code.
+    /// Both streams share the same pipe, so the ordering of the combined stream is only as good as
+    /// the flushing of the child process (e.g. a child that buffers its stdout may print it after its stderr).
+    ///
+    /// It does not honor [`timeout`], [`stdin_bytes`] or [`inherit_stdio`], the combined stream is always captured.
+    ///
+    /// # Errors
+    ///
+    /// [`crate::process::Error::FailedToExecProcess`] - Originates when the execution of Command fails.
+    /// [`crate::process::Error::Interrupted`] - Originates when the execution of the command is interrupted, e.g. by a signal.
+    ///
+    /// [`exec`]: crate::process::Process::exec
+    /// [`exec_combined`]: crate::process::Process::exec_combined
+    /// [`timeout`]: crate::process::Process::timeout
+    /// [`stdin_bytes`]: crate::process::Process::stdin_bytes
+    /// [`inherit_stdio`]: crate::process::Process::inherit_stdio
+    /// [`crate::process::Error::FailedToExecProcess`]: crate::process::Error::FailedToExecProcess
+    /// [`crate::process::Error::Interrupted`]: crate::process::Error::Interrupted
+    /// [`std::io::stdout`]: std::io::stdout
+    /// [`std::io::stderr`]: std::io::stderr
+    /// [`std::process::Command`]: std::process::Command
+    pub fn exec_combined(&mut self) -> Result<(Vec<u8>, i32), Error> {
+        self.validate()?;
+        self.log_exec();
+
+        let (mut reader, writer) = std::io::pipe()
+            .map_err(|err| Error::FailedToExecProcess(self.program().into(), err))?;
+        let writer_clone = writer
+            .try_clone()
+            .map_err(|err| Error::FailedToExecProcess(self.program().into(), err))?;
+
+        let spawn_result = self.cmd.stdout(writer_clone).stderr(writer).spawn();
+
+        // The command holds the write ends of the pipe, they need to be closed to receive EOF.
+        self.cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
+
+        let mut child =
+            spawn_result.map_err(|err| Error::FailedToExecProcess(self.program().into(), err))?;
+        let _forwarding = Forwarding::to(&child);
+
+        let mut combined = Vec::new();
+        reader
+            .read_to_end(&mut combined)
+            .map_err(|err| Error::FailedToExecProcess(self.program().into(), err))?;
+
+        let status = child
+            .wait()
+            .map_err(|err| Error::FailedToExecProcess(self.program().into(), err))?;
+        debug!("the process exited with {}", status);
+
+        let ecode = status
+            .code()
+            .ok_or_else(|| Error::Interrupted(signal(&status)))?;
+
+        Ok((combined, ecode))
+    }
+
+    /// [`spawn_detached`] validates the program of the user provided [`std::process::Command`],
+    /// executes the command without waiting it and returns its PID.
+    ///
+    /// The output streams of the command are discarded, so it does not hold the terminal of the caller.
+    /// This comes with a tradeoff: since the command is not waited, a failed command is not reported
+    /// as [`crate::process::Error::Exec`] unless it is checked via [`try_wait_detached`], and the command is not reaped until then.
+    /// The command keeps running after the [`crate::process::Process`] is dropped, unless [`kill_on_drop`] is set.
+    ///
+    /// # Errors
+    ///
+    /// [`crate::process::Error::FailedToExecProcess`] - Originates when the execution of Command fails.
+    ///
+    /// [`spawn_detached`]: crate::process::Process::spawn_detached
+    /// [`kill_on_drop`]: crate::process::Process::kill_on_drop
+    /// [`try_wait_detached`]: crate::process::Process::try_wait_detached
+    /// [`crate::process::Process`]: crate::process::Process
+    /// [`crate::process::Error::Exec`]: crate::process::Error::Exec
+    /// [`crate::process::Error::FailedToExecProcess`]: crate::process::Error::FailedToExecProcess
+    /// [`std::process::Command`]: std::process::Command
+    pub fn spawn_detached(&mut self) -> Result<u32, Error> {
+        self.validate()?;
+        self.log_exec();
+
+        let child = self
+            .cmd
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|err| Error::FailedToExecProcess(self.program().into(), err))?;
+
+        let pid = child.id();
+        info!("detached from the process {}", pid);
+
+        // Kept to be checked by try_wait_detached and to be killed on drop.
+        self.children.push(child);
+        Ok(pid)
+    }
+
+    /// [`try_wait_detached`] checks the commands started by [`spawn_detached`] without waiting them,
+    /// e.g. to notice a detached command that fails right away.
+    ///
+    /// It returns `true` once all of them have exited successfully, and `false` while any of them is still running.
+    /// The exited commands are reaped, so they are not checked again.
+    ///
+    /// # Errors
+    ///
+    /// [`crate::process::Error::FailedToExecProcess`] - Originates when the status of a command cannot be checked.
+    /// [`crate::process::Error::Interrupted`] - Originates when a command is interrupted, e.g. by a signal.
+    /// [`crate::process::Error::Exec`] - Originates when a command exits with non-zero.
+    /// It holds an empty stderr since the output streams of a detached command are discarded.
+    ///
+    /// [`try_wait_detached`]: crate::process::Process::try_wait_detached
+    /// [`spawn_detached`]: crate::process::Process::spawn_detached
+    /// [`crate::process::Error::FailedToExecProcess`]: crate::process::Error::FailedToExecProcess
+    /// [`crate::process::Error::Interrupted`]: crate::process::Error::Interrupted
+    /// [`crate::process::Error::Exec`]: crate::process::Error::Exec
+    pub fn try_wait_detached(&mut self) -> Result<bool, Error> {
+        let mut running = false;
+
+        for mut child in std::mem::take(&mut self.children) {
+            let wait_result = child
+                .try_wait()
+                .map_err(|err| Error::FailedToExecProcess(self.program().into(), err));
+
+            match wait_result {
+                Ok(None) => {
+                    running = true;
+                    self.children.push(child);
+                }
+                Ok(Some(status)) => {
+                    let output = Output {
+                        status,
+                        stdout: Vec::new(),
+                        stderr: Vec::new(),
+                    };
+                    into_result(self.wrapped_program(), output)?;
+                }
+                Err(err) => {
+                    self.children.push(child);
+                    return Err(err);
+                }
+            }
+        }
+
+        Ok(!running)
+    }
+
+    /// [`exec_with_timeout`] is the bounded counterpart of [`exec`].
+    /// It behaves the same as [`exec`], except that it waits the command up to the given timeout.
+    ///
+    /// When the timeout is exceeded, the command is killed and reaped before returning,
+    /// and the output that is collected so far is discarded.
+    ///
+    /// It is deprecated in favor of [`timeout`], which bounds [`exec`] and the other methods built on [`output`] the same way,
+    /// e.g. every attempt of [`exec_with_retries`].
+    ///
+    /// # Errors
+    ///
+    /// The errors are the same with [`exec`], along with:
+    ///
+    /// [`crate::process::Error::TimedOut`] - Originates when the command does not finish within the given timeout.
+    ///
+    /// [`exec`]: crate::process::Process::exec
+    /// [`exec_with_timeout`]: crate::process::Process::exec_with_timeout
+    /// [`timeout`]: crate::process::Process::timeout
+    /// [`output`]: crate::process::Process::output
+    /// [`exec_with_retries`]: crate::process::Process::exec_with_retries
+    /// [`crate::process::Error::TimedOut`]: crate::process::Error::TimedOut
+    #[deprecated(note = "use `Process::timeout` along with `Process::exec` instead")]
+    pub fn exec_with_timeout(&mut self, timeout: Duration) -> Result<Vec<u8>, Error> {
+        let configured_timeout = self.timeout.replace(timeout);
+        let exec_result = self.exec();
+        self.timeout = configured_timeout;
+
+        exec_result
+    }
+
+    /// [`exec_async`] is the asynchronous counterpart of [`exec`], built on [`tokio::process::Command`].
+    /// It validates the program, executes the command and awaits its output without blocking the runtime.
+    ///
+    /// It requires the `async` feature, and must be awaited within a tokio runtime.
+    ///
+    /// # Errors
+    ///
+    /// The errors are the same with [`exec`].
+    ///
+    /// [`exec`]: crate::process::Process::exec
+    /// [`exec_async`]: crate::process::Process::exec_async
+    /// [`tokio::process::Command`]: tokio::process::Command
+    #[cfg(feature = "async")]
+    pub async fn exec_async(&mut self) -> Result<Vec<u8>, Error> {
+        self.validate()?;
+        self.log_exec();
+
+        let mut cmd = tokio::process::Command::new(self.program());
+        cmd.args(self.get_args());
+
+        for (key, val) in self.cmd.get_envs() {
+            match val {
+                Some(val) => cmd.env(key, val),
+                None => cmd.env_remove(key),
+            };
+        }
+
+        if let Some(dir) = self.cmd.get_current_dir() {
+            cmd.current_dir(dir);
+        }
+
+        let proc_output = cmd
+            .kill_on_drop(self.kill_on_drop)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .output()
+            .await
+            .map_err(|err| Error::FailedToExecProcess(self.program().into(), err))?;
+
+        into_result(self.wrapped_program(), proc_output)
+    }
+
+    /// [`exec_with_retries`] is the persistent counterpart of [`exec`].
+    /// It behaves the same as [`exec`], except that it re-runs the command up to `retries` times
+    /// when it exits with a non-zero code, waiting `backoff` between the attempts.
+    ///
+    /// Only [`crate::process::Error::Exec`] is retried, the other errors are returned right away
+    /// since they do not get better with another attempt.
+    ///
+    /// # Errors
+    ///
+    /// The errors are the same with [`exec`]. When all attempts fail, the error of the last attempt is returned.
+    ///
+    /// [`exec`]: crate::process::Process::exec
+    /// [`exec_with_retries`]: crate::process::Process::exec_with_retries
+    /// [`crate::process::Error::Exec`]: crate::process::Error::Exec
+    pub fn exec_with_retries(&mut self, retries: u32, backoff: Duration) -> Result<Vec<u8>, Error> {
+        let mut attempt = 0;
+
+        loop {
+            match self.exec() {
+                Err(Error::Exec { code, .. }) if attempt < retries => {
+                    attempt += 1;
+                    warn!(
+                        "{} exited with {code}, retrying ({attempt}/{retries}) in {backoff:?}",
+                        self.command_line()
+                    );
+                    thread::sleep(backoff);
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// Abstracts the execution of a [`crate::process::Process`], so that its callers can be tested
+/// without running the commands, e.g. by recording the command lines instead.
+///
+/// It is implemented for the closures, the real runner is usually a call to [`exec`]:
+///
+/// ```
+/// use hpm::process::{Process, Runner};
+/// use std::process::Command;
+///
+/// let mut runner = |process: &mut Process| process.exec();
+///
+/// let mut process = Process::new(Command::new("echo"));
+/// process.arg("hi");
+/// assert_eq!(runner.run(&mut process).unwrap(), b"hi\n");
+/// ```
+///
+/// [`crate::process::Process`]: crate::process::Process
+/// [`exec`]: crate::process::Process::exec
+pub trait Runner {
+    /// Executes the given [`crate::process::Process`] and returns its [`std::io::stdout`] stream,
+    /// with the same errors as [`exec`].
+    ///
+    /// [`crate::process::Process`]: crate::process::Process
+    /// [`exec`]: crate::process::Process::exec
+    /// [`std::io::stdout`]: std::io::stdout
+    fn run(&mut self, process: &mut Process) -> Result<Vec<u8>, Error>;
+}
+
+impl<F> Runner for F
+where
+    F: FnMut(&mut Process) -> Result<Vec<u8>, Error>,
+{
+    fn run(&mut self, process: &mut Process) -> Result<Vec<u8>, Error> {
+        self(process)
+    }
+}
+
+/// Renders the command line the same way as [`dry_run`], e.g. for `info!("running {}", process)`.
+///
+/// [`dry_run`]: crate::process::Process::dry_run
+impl std::fmt::Display for Process {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.dry_run())
+    }
+}
+
+impl Drop for Process {
+    fn drop(&mut self) {
+        if !self.kill_on_drop {
+            return;
+        }
+
+        for child in &mut self.children {
+            // The child might have exited already, hence the ignored errors.
+            if let Ok(None) = child.try_wait() {
+                info!("killing the process {}", child.id());
+                let _ = child.kill();
+                let _ = child.wait();
+            }
+        }
+    }
+}
+
+/// Checks whether the given binary is accessible on the host, similar to `which`.
+///
+/// ```
+/// use hpm::process::binary_exists;
+///
+/// assert!(binary_exists("echo"));
+/// assert!(!binary_exists("this-binary-does-not-exist"));
+/// ```
+pub fn binary_exists(name: impl AsRef<OsStr>) -> bool {
+    which::which(name).is_ok()
+}
+
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// The memoized `$PATH` lookups of [`validate`], keyed by the program, see [`clear_lookup_cache`].
+///
+/// [`validate`]: crate::process::Process::validate
+/// [`clear_lookup_cache`]: crate::process::clear_lookup_cache
+static LOOKUP_CACHE: OnceLock<Mutex<HashMap<OsString, bool>>> = OnceLock::new();
+
+fn lookup_cache() -> MutexGuard<'static, HashMap<OsString, bool>> {
+    // The map stays consistent even if a holder panicked, so the poisoning is ignored.
+    LOOKUP_CACHE
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Clears the `$PATH` lookups that [`validate`] memoizes for each program,
+/// e.g. after a missing program is installed or `$PATH` is changed by a long-running caller.
+///
+/// ```
+/// use hpm::Process;
+///
+/// let process = Process::from_args("echo", ["hi"]);
+/// assert!(process.validate().is_ok());
+///
+/// // The next validate looks echo up in $PATH again.
+/// hpm::process::clear_lookup_cache();
+/// assert!(process.validate().is_ok());
+/// ```
+///
+/// [`validate`]: crate::process::Process::validate
+pub fn clear_lookup_cache() {
+    lookup_cache().clear();
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    path.metadata()
+        .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// Quotes the given part of a command line for a POSIX shell, unless it is made of the safe characters only.
+fn shell_quote(part: &str) -> String {
+    let is_safe = |ch: char| ch.is_ascii_alphanumeric() || "_-+=@%:,./".contains(ch);
+
+    if !part.is_empty() && part.chars().all(is_safe) {
+        return part.to_owned();
+    }
+
+    // A single quote cannot be escaped within single quotes, hence it closes, escapes and reopens them.
+    format!("'{}'", part.replace('\'', r"'\''"))
+}
+
+/// The PID of the command that is being waited, zero when there is none.
+/// It is the one that receives the signals of the caller after [`forward_signals`].
+///
+/// [`forward_signals`]: crate::process::forward_signals
+static FORWARD_PID: AtomicU32 = AtomicU32::new(0);
+
+/// Whether [`forward_signals`] has installed its handlers, so that they are installed once per process.
+///
+/// [`forward_signals`]: crate::process::forward_signals
+#[cfg(all(feature = "signals", unix))]
+static FORWARDING_SIGNALS: std::sync::atomic::AtomicBool =
+    std::sync::atomic::AtomicBool::new(false);
+
+/// Marks the given child as the target of the forwarded signals until it is dropped.
+struct Forwarding;
+
+impl Forwarding {
+    fn to(child: &Child) -> Self {
+        FORWARD_PID.store(child.id(), Ordering::SeqCst);
+        Forwarding
+    }
+}
+
+impl Drop for Forwarding {
+    fn drop(&mut self) {
+        FORWARD_PID.store(0, Ordering::SeqCst);
+    }
+}
+
+/// Forwards SIGINT and SIGTERM of the caller to the command that is being waited,
+/// so that it does not linger after Ctrl-C. The interrupted command is then reported
+/// as [`crate::process::Error::Interrupted`].
+///
+/// When no command is being waited, the caller exits with 128 + the signal number (e.g. 130 for SIGINT),
+/// as it would without a handler. Only a single command is tracked, i.e. the one that is waited last.
+///
+/// The handlers are installed once per process, so calling it again is a no-op.
+///
+/// It requires the `signals` feature, and is available on Unix only.
+///
+/// ```
+/// use hpm::{Error, Process};
+/// use std::process::Command;
+///
+/// hpm::process::forward_signals().unwrap();
+/// hpm::process::forward_signals().unwrap();
+///
+/// // The command stands for a Ctrl-C while it is running.
+/// let mut cmd = Command::new("sh");
+/// cmd.args(["-c", "sleep 0.2; kill -INT $PPID; exec sleep 5"]);
+///
+/// let exec_err = Process::new(cmd).exec().unwrap_err();
+/// assert!(matches!(exec_err, Error::Interrupted(Some(2))));
+/// assert_eq!(exec_err.code(), 130);
+/// ```
+///
+/// # Errors
+///
+/// Originates when the signal handlers cannot be installed.
+///
+/// [`crate::process::Error::Interrupted`]: crate::process::Error::Interrupted
+#[cfg(all(feature = "signals", unix))]
+pub fn forward_signals() -> std::io::Result<()> {
+    if FORWARDING_SIGNALS
+        .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+        .is_err()
+    {
+        return Ok(());
+    }
+
+    for signal in [libc::SIGINT, libc::SIGTERM] {
+        // SAFETY: the action only calls async-signal-safe functions, i.e. atomics, kill(2) and _exit(2).
+        unsafe {
+            let register_result = signal_hook_registry::register(signal, move || match FORWARD_PID
+                .load(Ordering::SeqCst)
+            {
+                0 => libc::_exit(128 + signal),
+                pid => {
+                    libc::kill(pid as libc::pid_t, signal);
+                }
+            });
+
+            // Let the next call try again, a handler that is already registered stays as is.
+            if let Err(err) = register_result {
+                FORWARDING_SIGNALS.store(false, Ordering::SeqCst);
+                return Err(err);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn drain(stream: Option<impl Read + Send + 'static>) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buf = Vec::new();
+        if let Some(mut stream) = stream {
+            let _ = stream.read_to_end(&mut buf);
+        }
+        buf
+    })
+}
+
+fn into_result(program: &OsStr, output: Output) -> Result<Vec<u8>, Error> {
+    debug!("the process exited with {}", output.status);
+
+    let ecode = output
+        .status
+        .code()
+        .ok_or_else(|| Error::Interrupted(signal(&output.status)))?;
+
+    if output.status.success() {
+        return Ok(output.stdout);
+    }
+
+    Err(Error::Exec {
+        program: program.to_os_string(),
+        code: ecode,
+        stdout: output.stdout,
+        stderr: output.stderr,
+    })
+}
+
+/// Explains the exit codes of the `systemctl` actions, e.g. `poweroff` or `reboot`, which follow the LSB init script actions.
+/// The query verbs (e.g. `is-active`) use another table, see the "EXIT STATUS" section of systemctl(1), but they are not run via [`exec`].
+///
+/// [`exec`]: crate::process::Process::exec
+fn explain_exit_code(program: &OsStr, code: i32) -> Option<&'static str> {
+    if Path::new(program).file_name()? != "systemctl" {
+        return None;
+    }
+
+    match code {
+        1 => Some("generic or unspecified error"),
+        2 => Some("invalid or excess arguments"),
+        3 => Some("unimplemented feature"),
+        4 => Some("insufficient privilege"),
+        5 => Some("program is not installed"),
+        6 => Some("program is not configured"),
+        7 => Some("program is not running"),
+        _ => None,
+    }
+}
+
+#[cfg(unix)]
+fn signal(status: &ExitStatus) -> Option<i32> {
+    use std::os::unix::process::ExitStatusExt;
+    status.signal()
+}
+
+#[cfg(not(unix))]
+fn signal(_status: &ExitStatus) -> Option<i32> {
+    None
+}
+
+fn signal_name(signal: i32) -> Option<&'static str> {
+    match signal {
+        1 => Some("SIGHUP"),
+        2 => Some("SIGINT"),
+        3 => Some("SIGQUIT"),
+        6 => Some("SIGABRT"),
+        9 => Some("SIGKILL"),
+        13 => Some("SIGPIPE"),
+        14 => Some("SIGALRM"),
+        15 => Some("SIGTERM"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_return_proper_program_name() {
+        let cmd = Command::new("echo");
+        assert_eq!(Process::new(cmd).program(), "echo")
+    }
+
+    #[test]
+    fn should_display_the_quoted_command_line() {
+        let process = Process::from_args("shutdown", ["-r", "+5", "it's time"]);
+
+        assert_eq!(format!("{}", process), r"shutdown -r +5 'it'\''s time'");
+    }
+
+    #[test]
+    fn should_return_proper_command_line() {
+        let mut cmd = Command::new("systemctl");
+        cmd.arg("reboot");
+
+        assert_eq!(Process::new(cmd).command_line(), "systemctl reboot")
+    }
+
+    #[test]
+    fn should_quote_the_dry_run_command_line() {
+        let process = Process::from_args("wall", ["it's", "kernel upgrade", "", "a$b"]);
+
+        assert_eq!(
+            process.dry_run(),
+            r"wall 'it'\''s' 'kernel upgrade' '' 'a$b'"
+        );
+        assert_eq!(
+            Process::from_args("systemctl", ["reboot"]).dry_run(),
+            "systemctl reboot"
+        );
+    }
+
+    #[test]
+    fn should_wrap_command_with_sudo() {
+        let mut cmd = Command::new("systemctl");
+        cmd.arg("poweroff");
+
+        let process = Process::new(cmd).with_sudo();
+
+        assert_eq!(process.program(), "sudo");
+        assert_eq!(process.command_line(), "sudo systemctl poweroff");
+    }
+
+    #[test]
+    fn should_wrap_command_with_pkexec() {
+        let mut cmd = Command::new("systemctl");
+        cmd.arg("poweroff");
+
+        let process = Process::new(cmd).with_pkexec();
+
+        assert_eq!(process.command_line(), "pkexec systemctl poweroff");
+    }
+
+    #[test]
+    fn should_pass_extra_args_to_child_process() {
+        let mut process = Process::new(Command::new("echo"));
+        process.arg("hi");
+
+        let exec_result = process.exec();
+
+        assert!(exec_result.is_ok_and(|stdout| stdout == b"hi\n"));
+    }
+
+    #[test]
+    fn should_exec_child_process_in_given_dir() {
+        let dir = std::env::temp_dir().canonicalize().unwrap();
+
+        let mut process = Process::new(Command::new("pwd"));
+        process.current_dir(&dir);
+
+        let exec_result = process.exec();
+
+        assert!(exec_result.is_ok_and(|stdout| {
+            String::from_utf8_lossy(&stdout).trim_end() == dir.to_string_lossy()
+        }));
+    }
+
+    #[test]
+    fn should_pass_env_to_child_process() {
+        let mut process = Process::new(Command::new("env"));
+        process.env("HPM_TEST_ENV", "hpm");
+
+        let exec_result = process.exec();
+
+        assert!(exec_result.is_ok_and(|stdout| {
+            String::from_utf8_lossy(&stdout)
+                .lines()
+                .any(|line| line == "HPM_TEST_ENV=hpm")
+        }));
+    }
+
+    #[test]
+    fn should_clear_env_of_child_process() {
+        let mut process = Process::new(Command::new("env"));
+        process.env_clear().env("HPM_TEST_ENV", "hpm");
+
+        let exec_result = process.exec();
+
+        assert!(exec_result.is_ok_and(|stdout| stdout == b"HPM_TEST_ENV=hpm\n"));
+    }
+
+    #[test]
+    fn should_not_exec_nonexistent_binaries() {
+        let cmd = Command::new("this-binary-does-not-exist");
+        let mut process = Process::new(cmd);
+
+        let validate_result = process.validate();
+        assert!(validate_result.is_err());
+
+        let exec_result = process.exec();
+        assert!(exec_result.is_err());
+
+        let validate_err_str = format!("{}", validate_result.unwrap_err());
+        let exec_err_str = format!("{}", exec_result.unwrap_err());
+
+        assert_eq!(validate_err_str, exec_err_str);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn should_validate_absolute_paths_without_path_lookup() {
+        assert!(Process::new(Command::new("/bin/echo")).validate().is_ok());
+        assert!(matches!(
+            Process::new(Command::new("/bin/this-binary-does-not-exist")).validate(),
+            Err(Error::BinaryDoesNotExist(_))
+        ));
+        // Neither a directory nor a non-executable file can be executed.
+        assert!(Process::new(Command::new("/bin")).validate().is_err());
+
+        let file = std::env::temp_dir().join(format!("hpm-not-executable-{}", std::process::id()));
+        std::fs::write(&file, "").unwrap();
+        let validate_result = Process::new(Command::new(&file)).validate();
+        let _ = std::fs::remove_file(&file);
+
+        assert!(validate_result.is_err());
+    }
+
+    #[test]
+    fn should_not_exec_empty_programs() {
+        let mut process = Process::new(Command::new(""));
+
+        assert!(matches!(process.validate(), Err(Error::EmptyProgram)));
+        assert!(matches!(process.exec(), Err(Error::EmptyProgram)));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn should_report_permission_denied_on_spawn() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("hpm-permission-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // The script is executable, but its interpreter is not, which fails with EACCES even for root.
+        let interpreter = dir.join("interpreter");
+        std::fs::write(&interpreter, "").unwrap();
+        let script = dir.join("script");
+        std::fs::write(&script, format!("#!{}\n", interpreter.display())).unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let exec_result = Process::new(Command::new(&script)).exec();
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert!(exec_result.is_err_and(|err| {
+            matches!(&err, Error::FailedToExecProcess(_, io_err) if io_err.kind() == std::io::ErrorKind::PermissionDenied)
+                && err.code() == 1
+        }));
+    }
+
+    #[test]
+    fn should_report_missing_binaries_on_spawn_without_validation() {
+        let mut process = Process::new(Command::new("this-binary-does-not-exist"));
+        let exec_result = process.exec_unchecked();
+
+        assert!(exec_result.is_err_and(|err| {
+            matches!(&err, Error::FailedToExecProcess(_, io_err) if io_err.kind() == std::io::ErrorKind::NotFound)
+        }));
+
+        let mut process = Process::from_args("echo", ["hi"]);
+        assert!(
+            process
+                .exec_unchecked()
+                .is_ok_and(|stdout| stdout == b"hi\n")
+        );
+    }
+
+    #[test]
+    fn should_propagate_stderr_of_child_process() {
+        let mut cmd = Command::new("ls");
+        cmd.arg("this-file-does-not-exist");
+
+        let mut process = Process::new(cmd);
+        let exec_result = process.exec();
+
+        assert!(exec_result.is_err_and(|err| {
+            if let Error::Exec { code, stderr, .. } = err {
+                code > 0 && stderr.bytes().count() > 0
+            } else {
+                false
+            }
+        }));
+    }
+
+    #[test]
+    fn should_propagate_stdout_of_child_process() {
+        let cmd = Command::new("ls");
 
         let mut process = Process::new(cmd);
         let exec_result = process.exec();
 
         assert!(exec_result.is_ok_and(|stdout| { stdout.bytes().count() > 0 }));
     }
+
+    #[test]
+    fn should_propagate_exit_code_of_streamed_child_process() {
+        let mut cmd = Command::new("ls");
+        cmd.arg("this-file-does-not-exist");
+
+        let mut process = Process::new(cmd);
+        let exec_result = process.exec_streaming();
+
+        assert!(exec_result.is_err_and(|err| matches!(err, Error::Exec { code, .. } if code > 0)));
+    }
+
+    #[test]
+    fn should_kill_child_process_after_timeout() {
+        let mut cmd = Command::new("sleep");
+        cmd.arg("5");
+
+        let mut process = Process::new(cmd);
+        let exec_result = process.timeout(Duration::from_millis(100)).exec();
+
+        assert!(exec_result.is_err_and(|err| matches!(err, Error::TimedOut(_))));
+    }
+
+    #[test]
+    fn should_propagate_stdout_of_child_process_within_timeout() {
+        let mut cmd = Command::new("echo");
+        cmd.arg("hi");
+
+        let mut process = Process::new(cmd);
+        let exec_result = process.timeout(Duration::from_secs(5)).exec();
+
+        assert!(exec_result.is_ok_and(|stdout| stdout == b"hi\n"));
+    }
+
+    #[test]
+    fn should_bound_every_attempt_by_the_timeout() {
+        let mut process = Process::from_args("sleep", ["5"]);
+        process.timeout(Duration::from_millis(100));
+
+        let started = Instant::now();
+        let exec_result = process.exec_with_retries(2, Duration::ZERO);
+
+        assert!(exec_result.is_err_and(|err| matches!(err, Error::TimedOut(_))));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn should_skip_the_lookup_of_a_cached_program() {
+        // The program does not exist, so only the cache can resolve it.
+        let program = format!("hpm-cached-program-{}", std::process::id());
+        lookup_cache().insert(program.clone().into(), true);
+
+        let process = Process::from_args(&program, [] as [&str; 0]);
+        assert!(process.validate().is_ok());
+
+        clear_lookup_cache();
+        assert!(matches!(
+            process.validate(),
+            Err(Error::BinaryDoesNotExist(_))
+        ));
+        assert_eq!(lookup_cache().get(OsStr::new(&program)), Some(&false));
+    }
+
+    #[test]
+    fn should_not_cache_the_lookup_of_a_relative_path() {
+        let program = format!("./hpm-relative-program-{}", std::process::id());
+        lookup_cache().insert(program.clone().into(), true);
+
+        let process = Process::from_args(&program, [] as [&str; 0]);
+        assert!(matches!(
+            process.validate(),
+            Err(Error::BinaryDoesNotExist(_))
+        ));
+        assert_ne!(lookup_cache().get(OsStr::new(&program)), Some(&false));
+    }
+
+    #[test]
+    fn should_report_the_duration_of_child_process() {
+        let mut process = Process::from_args("sleep", ["0.2"]);
+        let (stdout, elapsed) = process.exec_timed().unwrap();
+
+        assert!(stdout.is_empty());
+        assert!(elapsed >= Duration::from_millis(200));
+    }
+
+    #[test]
+    fn should_not_capture_inherited_stdio() {
+        let mut process = Process::from_args("echo", ["hi"]);
+        process.inherit_stdio(true);
+
+        assert!(process.exec().is_ok_and(|stdout| stdout.is_empty()));
+
+        process.inherit_stdio(false);
+        assert!(process.exec().is_ok_and(|stdout| stdout == b"hi\n"));
+    }
+
+    #[test]
+    fn should_keep_timeout_across_executions() {
+        let mut process = Process::from_args("sleep", ["5"]);
+        process.timeout(Duration::from_millis(100));
+
+        assert!(
+            process
+                .exec()
+                .is_err_and(|err| matches!(err, Error::TimedOut(_)))
+        );
+        assert!(
+            process
+                .output()
+                .is_err_and(|err| matches!(err, Error::TimedOut(_)))
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn should_abort_when_pre_exec_fails() {
+        let mut process = Process::from_args("true", [] as [&str; 0]);
+
+        // SAFETY: the closure only builds an error from a raw OS error code.
+        unsafe { process.pre_exec(|| Err(std::io::Error::from_raw_os_error(1))) };
+
+        assert!(
+            process
+                .exec()
+                .is_err_and(|err| matches!(err, Error::FailedToExecProcess(_, _)))
+        );
+    }
+
+    #[test]
+    fn should_display_non_utf8_stderr() {
+        let err = Error::Exec {
+            program: "ls".into(),
+            code: 1,
+            stdout: Vec::new(),
+            stderr: vec![0xff, 0xfe],
+        };
+        assert_eq!(format!("{err}"), "ls: \u{FFFD}\u{FFFD}");
+    }
+
+    #[test]
+    fn should_not_truncate_exit_codes() {
+        // Unix shells can only exit with 0-255, whereas Windows keeps the full 32-bit code.
+        #[cfg(unix)]
+        let (program, args, expected_code) = ("sh", ["-c", "exit 200"], 200);
+        #[cfg(windows)]
+        let (program, args, expected_code) = ("cmd", ["/C", "exit 300"], 300);
+
+        let mut cmd = Command::new(program);
+        cmd.args(args);
+
+        let mut process = Process::new(cmd);
+        let exec_result = process.exec();
+
+        assert!(
+            exec_result
+                .is_err_and(|err| matches!(err, Error::Exec { code, .. } if code == expected_code))
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn should_report_the_interrupting_signal() {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "kill -TERM $$"]);
+
+        let mut process = Process::new(cmd);
+        let exec_err = process.exec().unwrap_err();
+
+        assert!(matches!(exec_err, Error::Interrupted(Some(15))));
+        assert_eq!(format!("{exec_err}"), "interrupted by signal 15 (SIGTERM)");
+    }
+
+    #[test]
+    fn should_combine_stdout_and_stderr_of_child_process() {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "echo out; echo err >&2; exit 3"]);
+
+        let mut process = Process::new(cmd);
+        let exec_result = process.exec_combined();
+
+        assert!(exec_result.is_ok_and(|(combined, ecode)| combined == b"out\nerr\n" && ecode == 3));
+    }
+
+    #[test]
+    fn should_return_pid_of_detached_child_process() {
+        let mut cmd = Command::new("sleep");
+        cmd.arg("0");
+
+        let mut process = Process::new(cmd);
+        let spawn_result = process.spawn_detached();
+
+        assert!(spawn_result.is_ok_and(|pid| pid > 0));
+    }
+
+    #[test]
+    fn should_report_the_failure_of_detached_child_process() {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg("exit 3");
+
+        let mut process = Process::new(cmd);
+        process.spawn_detached().unwrap();
+
+        let started = Instant::now();
+        let wait_result = loop {
+            match process.try_wait_detached() {
+                Ok(false) if started.elapsed() < Duration::from_secs(5) => {
+                    thread::sleep(Duration::from_millis(10))
+                }
+                result => break result,
+            }
+        };
+
+        assert!(matches!(wait_result, Err(Error::Exec { code: 3, .. })));
+        assert!(process.try_wait_detached().is_ok_and(|exited| exited));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn should_kill_detached_child_process_on_drop() {
+        let mut cmd = Command::new("sleep");
+        cmd.arg("5");
+
+        let mut process = Process::new(cmd);
+        process.kill_on_drop(true);
+        let pid = process.spawn_detached().unwrap();
+        drop(process);
+
+        assert!(!Path::new(&format!("/proc/{pid}")).exists());
+    }
+
+    #[test]
+    fn should_explain_exit_codes_of_systemctl() {
+        let err = Error::Exec {
+            program: "/usr/bin/systemctl".into(),
+            code: 4,
+            stdout: Vec::new(),
+            stderr: b"Access denied".to_vec(),
+        };
+        assert_eq!(
+            format!("{err}"),
+            "/usr/bin/systemctl: 4: insufficient privilege: Access denied"
+        );
+
+        // loginctl has no units, and does not follow the same table.
+        let err = Error::Exec {
+            program: "loginctl".into(),
+            code: 4,
+            stdout: Vec::new(),
+            stderr: b"loginctl failed".to_vec(),
+        };
+        assert_eq!(format!("{err}"), "loginctl: loginctl failed");
+
+        let err = Error::Exec {
+            program: "ls".into(),
+            code: 4,
+            stdout: Vec::new(),
+            stderr: b"ls failed".to_vec(),
+        };
+        assert_eq!(format!("{err}"), "ls: ls failed");
+    }
+
+    #[test]
+    fn should_report_the_failed_program_of_wrapped_commands() {
+        let mut cmd = Command::new("ls");
+        cmd.arg("this-file-does-not-exist");
+
+        let mut process = Process::new(cmd).wrap("env");
+        let exec_result = process.exec();
+
+        assert!(exec_result.is_err_and(|err| {
+            matches!(&err, Error::Exec { program, .. } if program == "ls")
+                && format!("{err}").starts_with("ls: ")
+        }));
+    }
+
+    #[test]
+    fn should_return_the_exit_code_of_a_successful_command() {
+        let mut cmd = Command::new("echo");
+        cmd.arg("hello");
+
+        let mut process = Process::new(cmd);
+        let exec_result = process.exec_with_code();
+
+        assert!(exec_result.is_ok_and(|(stdout, ecode)| stdout == b"hello\n" && ecode == 0));
+    }
+
+    #[test]
+    fn should_return_the_exit_code_of_a_failed_command() {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg("echo partial; exit 4");
+
+        let mut process = Process::new(cmd);
+        let exec_result = process.exec_with_code();
+
+        assert!(exec_result.is_ok_and(|(stdout, ecode)| stdout == b"partial\n" && ecode == 4));
+    }
+
+    #[test]
+    fn should_retry_until_the_command_succeeds() {
+        let counter = std::env::temp_dir().join(format!("hpm-retries-{}", std::process::id()));
+        let _ = std::fs::remove_file(&counter);
+
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c")
+            .arg(r#"echo x >> "$0"; [ "$(wc -l < "$0")" -ge 3 ] && echo done"#)
+            .arg(&counter);
+
+        let mut process = Process::new(cmd);
+        let exec_result = process.exec_with_retries(2, Duration::ZERO);
+        let _ = std::fs::remove_file(&counter);
+
+        assert!(exec_result.is_ok_and(|stdout| stdout == b"done\n"));
+    }
+
+    #[test]
+    fn should_return_the_last_error_when_retries_are_exhausted() {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg("exit 3");
+
+        let mut process = Process::new(cmd);
+        let exec_result = process.exec_with_retries(2, Duration::ZERO);
+
+        assert!(exec_result.is_err_and(|err| matches!(err, Error::Exec { code: 3, .. })));
+    }
+
+    #[test]
+    fn should_not_retry_nonexistent_binaries() {
+        let cmd = Command::new("this-binary-does-not-exist");
+
+        let mut process = Process::new(cmd);
+        let exec_result = process.exec_with_retries(2, Duration::from_secs(60));
+
+        assert!(exec_result.is_err_and(|err| matches!(err, Error::BinaryDoesNotExist(_))));
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn should_exec_async() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg("echo $GREETING").env("GREETING", "hello");
+
+        let mut process = Process::new(cmd);
+        let exec_result = runtime.block_on(process.exec_async());
+
+        assert!(exec_result.is_ok_and(|stdout| stdout == b"hello\n"));
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn should_return_exec_error_async() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg("exit 3");
+
+        let mut process = Process::new(cmd);
+        let exec_result = runtime.block_on(process.exec_async());
+
+        assert!(exec_result.is_err_and(|err| matches!(err, Error::Exec { code: 3, .. })));
+    }
+
+    #[test]
+    fn should_map_errors_to_exit_codes() {
+        let exec_err = |code| Error::Exec {
+            program: "systemctl".into(),
+            code,
+            stdout: Vec::new(),
+            stderr: vec![],
+        };
+
+        assert_eq!(exec_err(4).code(), 4);
+        assert_eq!(exec_err(256).code(), 255);
+        assert_eq!(Error::Interrupted(None).code(), 130);
+        assert_eq!(Error::TimedOut(Duration::from_secs(1)).code(), 124);
+    }
+
+    #[test]
+    fn should_return_the_raw_output_regardless_of_the_status() {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg("echo out; echo err >&2; exit 3");
+
+        let mut process = Process::new(cmd);
+        let output = process.output().unwrap();
+
+        assert_eq!(output.status.code(), Some(3));
+        assert_eq!(output.stdout, b"out\n");
+        assert_eq!(output.stderr, b"err\n");
+    }
+
+    #[test]
+    fn should_return_stderr_of_successful_child_process() {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg("echo out; echo warning >&2");
+
+        let mut process = Process::new(cmd);
+        let exec_result = process.exec_with_stderr();
+
+        assert!(
+            exec_result.is_ok_and(|(stdout, stderr)| stdout == b"out\n" && stderr == b"warning\n")
+        );
+
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg("echo err >&2; exit 3");
+
+        let exec_result = Process::new(cmd).exec_with_stderr();
+        assert!(exec_result.is_err_and(
+            |err| matches!(err, Error::Exec { code: 3, stderr, .. } if stderr == b"err\n")
+        ));
+    }
+
+    #[test]
+    fn should_report_the_stdout_of_failed_child_process() {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg("echo warning; exit 3");
+
+        let exec_result = Process::new(cmd).exec();
+        assert!(exec_result.is_err_and(
+            |err| matches!(err, Error::Exec { code: 3, stdout, .. } if stdout == b"warning\n")
+        ));
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn should_keep_stdout_of_failed_child_process() {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg("echo warning; exit 3");
+
+        let mut process = Process::new(cmd);
+        let exec_result = process.exec_keep_stdout();
+
+        assert!(exec_result.is_ok_and(|(stdout, ecode)| stdout == b"warning\n" && ecode == 3));
+    }
+
+    #[test]
+    fn should_pipe_the_given_bytes_to_stdin() {
+        let mut process = Process::new(Command::new("cat"));
+        process.stdin_bytes(b"hello".to_vec());
+
+        let exec_result = process.exec();
+
+        assert!(exec_result.is_ok_and(|stdout| stdout == b"hello"));
+    }
 }